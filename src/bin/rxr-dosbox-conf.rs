@@ -1,57 +1,464 @@
 extern crate clap;
+extern crate difference;
 extern crate rxr;
 
 use std::error::Error;
+use std::fs;
 use std::path::PathBuf;
 use std::process;
 
-use rxr::dosbox_config::DosboxConfig;
+use rxr::dosbox_config::{Dialect, DosboxConfig, Document, Format, MergeOptions};
 
-fn run(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
-    let source_path = args.value_of("source").map(PathBuf::from).unwrap();
-    let target_path = args.value_of("target").map(PathBuf::from).unwrap();
-    let destination_path = args.value_of("destination")
-        .map(PathBuf::from)
-        .unwrap_or(target_path.clone());
+/// Reads a config as a document, building one from the settings when the
+/// config is JSON or TOML.
+fn read_document(path: &PathBuf) -> Result<Document, Box<Error>> {
+    match Format::from_path(path) {
+        Format::Conf => Document::read(path),
+        _ => {
+            let mut document = Document::parse::<&str>(&[])?;
+            document.apply(&DosboxConfig::read(path)?);
+            Ok(document)
+        }
+    }
+}
+
+fn validate(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
+    let mut invalid = 0;
+
+    for path in args.values_of("configs").unwrap() {
+        let document = read_document(&PathBuf::from(path))
+            .map_err(|err| format!("{}: {}", path, err))?;
+        let config = document.config();
+        let issues = config.validate_as(dialect(args, &config)?);
+
+        for (section, key, lines) in document.duplicates() {
+            let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+            println!(
+                "{}: [{}] {}: set on lines {}, the last one wins",
+                path,
+                section,
+                key,
+                lines.join(", ")
+            );
+        }
+
+        for issue in &issues {
+            println!("{}: {}", path, issue);
+        }
+
+        invalid += issues.iter().filter(|issue| !issue.is_unknown()).count();
+    }
+
+    if invalid > 0 {
+        return Err(From::from(format!("found {} invalid setting(s)", invalid)));
+    }
+
+    Ok(())
+}
+
+fn merge_options(args: &clap::ArgMatches) -> Result<MergeOptions, Box<Error>> {
+    let mut options = MergeOptions::default();
+
+    if let Some(strategy) = args.value_of("strategy") {
+        options.strategy = strategy.parse()?;
+    }
+
+    if let Some(autoexec) = args.value_of("autoexec") {
+        options.autoexec = autoexec.parse()?;
+    }
+
+    for section in args.values_of("section").into_iter().flat_map(|values| values) {
+        let mut split = section.splitn(2, '=');
+
+        match (split.next(), split.next()) {
+            (Some(name), Some(strategy)) if !name.is_empty() => {
+                options
+                    .sections
+                    .insert(name.to_lowercase(), strategy.parse()?);
+            }
+            _ => {
+                return Err(From::from(format!(
+                    "invalid section strategy '{}', expected <section>=<strategy>",
+                    section
+                )))
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+/// Returns the dialect given on the command line, or the one detected from
+/// the config.
+fn dialect(args: &clap::ArgMatches, config: &DosboxConfig) -> Result<Dialect, Box<Error>> {
+    match args.value_of("dialect") {
+        Some(dialect) => Ok(dialect.parse()?),
+        None => Ok(Dialect::detect(config)),
+    }
+}
+
+/// Translates a config into the given dialect, warning about the settings
+/// that don't carry over.
+fn translate(config: &DosboxConfig, path: &PathBuf, to: Dialect) -> DosboxConfig {
+    let (translated, issues) = config.translate(Dialect::detect(config), to);
+
+    for issue in issues {
+        eprintln!("[rxr-dosbox-conf warning] {}: {}", path.display(), issue);
+    }
+
+    translated
+}
+
+/// Splits a `section.key` argument.
+fn setting_key(arg: &str) -> Result<(&str, &str), Box<Error>> {
+    let mut split = arg.splitn(2, '.');
+
+    match (split.next(), split.next()) {
+        (Some(section), Some(key)) if !section.is_empty() && !key.is_empty() => {
+            Ok((section, key))
+        }
+        _ => Err(From::from(format!(
+            "invalid setting '{}', expected <section>.<key>",
+            arg
+        ))),
+    }
+}
+
+/// Prints the lines that differ between two versions of a config, along with
+/// the section headers they belong to.
+fn print_diff(original: &str, changed: &str) {
+    let changeset = difference::Changeset::new(original, changed, "\n");
+
+    for diff in &changeset.diffs {
+        match *diff {
+            difference::Difference::Same(ref lines) => {
+                for line in lines.lines().filter(|line| line.trim().starts_with('[')) {
+                    println!("  {}", line);
+                }
+            }
+            difference::Difference::Rem(ref lines) => {
+                for line in lines.lines() {
+                    println!("- {}", line);
+                }
+            }
+            difference::Difference::Add(ref lines) => {
+                for line in lines.lines() {
+                    println!("+ {}", line);
+                }
+            }
+        }
+    }
+}
 
-    let source = DosboxConfig::read(&source_path)?;
-    let target = DosboxConfig::read(&target_path)?;
+/// Returns the output format given on the command line, or the one of the
+/// output file, or else the one of the target.
+fn output_format(args: &clap::ArgMatches, target_path: &PathBuf) -> Result<Format, Box<Error>> {
+    match (args.value_of("format"), args.value_of("output")) {
+        (Some(format), _) => Ok(format.parse()?),
+        (None, Some(output)) => Ok(Format::from_path(&output)),
+        (None, None) => Ok(Format::from_path(target_path)),
+    }
+}
 
-    println!("Source: {:#?}", source);
-    println!("Target: {:#?}", target);
+/// Renders a document, keeping its formatting when written as a DOSBox
+/// config.
+fn render(document: &Document, format: Format) -> Result<String, Box<Error>> {
+    match format {
+        Format::Conf => Ok(document.to_string()),
+        _ => document.config().export(format),
+    }
+}
 
-    let merged = source.merge(&target);
+/// Writes the processed document to the output file, back to the target
+/// after backing it up, or to stdout, optionally showing a diff against the
+/// original target.
+fn emit(
+    args: &clap::ArgMatches,
+    target_path: &PathBuf,
+    original: &Document,
+    document: &Document,
+) -> Result<(), Box<Error>> {
+    let format = output_format(args, target_path)?;
+    let changed = render(document, format)?;
 
-    println!("Merged: {:#?}", merged);
+    if args.is_present("diff") {
+        print_diff(&render(original, format)?, &changed);
+    }
 
-    merged.write(&destination_path)?;
+    if args.is_present("in-place") {
+        let mut backup_path = target_path.clone().into_os_string();
+        backup_path.push(args.value_of("backup-suffix").unwrap());
+
+        fs::copy(target_path, &backup_path)?;
+        fs::write(target_path, changed)?;
+    } else if let Some(output) = args.value_of("output") {
+        fs::write(output, changed)?;
+    } else if !args.is_present("diff") {
+        print!("{}", changed);
+    }
 
     Ok(())
 }
 
+fn get(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
+    let (section, key) = setting_key(args.value_of("setting").unwrap())?;
+    let mut merged = DosboxConfig::default();
+
+    for path in args.values_of("configs").unwrap() {
+        merged = merged.merge(&DosboxConfig::read(&PathBuf::from(path))?);
+    }
+
+    match merged.get(section, key) {
+        Some(val) => {
+            println!("{}", val);
+            Ok(())
+        }
+        None => Err(From::from(format!("{}.{} is not set", section, key))),
+    }
+}
+
+fn set(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
+    let assignment = args.value_of("assignment").unwrap();
+    let (setting, val) = match assignment.find('=') {
+        Some(index) => (&assignment[..index], &assignment[index + 1..]),
+        None => {
+            return Err(From::from(format!(
+                "invalid assignment '{}', expected <section>.<key>=<value>",
+                assignment
+            )))
+        }
+    };
+    let (section, key) = setting_key(setting)?;
+
+    let target_path = PathBuf::from(args.value_of("config").unwrap());
+    let mut document = read_document(&target_path)?;
+    let original = document.clone();
+
+    document.set(section, key, val);
+
+    emit(args, &target_path, &original, &document)
+}
+
+fn unset(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
+    let (section, key) = setting_key(args.value_of("setting").unwrap())?;
+
+    let target_path = PathBuf::from(args.value_of("config").unwrap());
+    let mut document = read_document(&target_path)?;
+    let original = document.clone();
+
+    if !document.unset(section, key) {
+        eprintln!("[rxr-dosbox-conf warning] {}.{} is not set", section, key);
+    }
+
+    emit(args, &target_path, &original, &document)
+}
+
+/// Merges the layered configs in order, keeping the formatting of the last
+/// one, the target. Every config is translated into the dialect of the first
+/// one, unless another is given.
+fn merge(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
+    let paths: Vec<PathBuf> = args.values_of("configs")
+        .unwrap()
+        .map(PathBuf::from)
+        .collect();
+    let (target_path, layers) = paths.split_last().unwrap();
+    let options = merge_options(args)?;
+
+    let configs = layers
+        .iter()
+        .map(DosboxConfig::read)
+        .collect::<Result<Vec<DosboxConfig>, Box<Error>>>()?;
+
+    let mut document = read_document(target_path)?;
+    let original = document.clone();
+    let dialect = dialect(args, configs.first().unwrap_or(&document.config()))?;
+    let mut target = translate(&document.config(), target_path, dialect);
+
+    if let Some(base) = args.value_of("mount-base") {
+        target.rebase_mounts(&PathBuf::from(base));
+    }
+
+    let mut merged = DosboxConfig::default();
+    for (config, path) in configs.iter().zip(layers) {
+        merged = merged.merge_with(&translate(config, path, dialect), &options);
+    }
+
+    let merged = if layers.is_empty() {
+        target
+    } else {
+        merged.merge_with(&target, &options)
+    };
+
+    for issue in merged.validate_as(dialect) {
+        eprintln!("[rxr-dosbox-conf warning] {}", issue);
+    }
+
+    document.apply_translated(&merged);
+
+    emit(args, target_path, &original, &document)
+}
+
+fn run(args: &clap::ArgMatches) -> Result<(), Box<Error>> {
+    match args.subcommand() {
+        ("validate", Some(args)) => validate(args),
+        ("get", Some(args)) => get(args),
+        ("set", Some(args)) => set(args),
+        ("unset", Some(args)) => unset(args),
+        _ => merge(args),
+    }
+}
+
 fn main() {
+    let configs_arg = clap::Arg::with_name("configs")
+        .required(true)
+        .multiple(true)
+        .value_name("config");
+
     let command_line = clap::App::new(
         "rxr-dosbox-conf - Rust eXtract and Run Dosbox Config Utility",
     ).version(env!("CARGO_PKG_VERSION"))
         .author("Stefan Alberts <stefan6573@gmail.com>")
         .about("Utility to process and merge dosbox configs.")
+        .setting(clap::AppSettings::SubcommandsNegateReqs)
+        .arg(
+            configs_arg
+                .clone()
+                .help("Config files to be merged in order, e.g. base, per-emulator and per-game. The last one is the target, whose formatting is kept. Files ending in .json or .toml are read as structured configs.")
+        )
+        .arg(
+            clap::Arg::with_name("output")
+                .help("Path to which the processed config will be written. Defaults to stdout.")
+                .long("output")
+                .short("o")
+                .takes_value(true)
+                .global(true)
+                .value_name("output")
+        )
+        .arg(
+            clap::Arg::with_name("in-place")
+                .help("Overwrite the target, keeping a backup of the original.")
+                .long("in-place")
+                .short("i")
+                .conflicts_with("output")
+                .global(true)
+        )
+        .arg(
+            clap::Arg::with_name("backup-suffix")
+                .help("Suffix of the backup made when editing in place.")
+                .long("backup-suffix")
+                .takes_value(true)
+                .default_value(".bak")
+                .global(true)
+                .value_name("suffix")
+        )
+        .arg(
+            clap::Arg::with_name("diff")
+                .help("Show the changes relative to the target.")
+                .long("diff")
+                .short("d")
+                .global(true)
+        )
+        .arg(
+            clap::Arg::with_name("format")
+                .help("Format of the processed config. Defaults to the extension of the output file, or of the target.")
+                .long("format")
+                .short("f")
+                .takes_value(true)
+                .possible_values(&["conf", "json", "toml"])
+                .global(true)
+                .value_name("format")
+        )
+        .arg(
+            clap::Arg::with_name("dialect")
+                .help("DOSBox flavour to write and validate for. Defaults to the one detected from the first config.")
+                .long("dialect")
+                .takes_value(true)
+                .possible_values(&["dosbox", "staging", "dosbox-x"])
+                .global(true)
+                .value_name("dialect")
+        )
         .arg(
-            clap::Arg::with_name("source")
-                .help("Source config file to be referenced.")
-                .required(true)
-                .value_name("source")
+            clap::Arg::with_name("strategy")
+                .help("How later settings are merged into the sections of earlier configs.")
+                .long("strategy")
+                .short("s")
+                .takes_value(true)
+                .possible_values(&["override", "keep-source", "replace-section"])
+                .value_name("strategy")
         )
         .arg(
-            clap::Arg::with_name("target")
-                .help("Target config file to be processed.")
-                .required(true)
-                .value_name("target")
+            clap::Arg::with_name("section")
+                .help("Strategy for a single section, e.g. 'cpu=keep-source'. Can be repeated.")
+                .long("section")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("section=strategy")
         )
         .arg(
-            clap::Arg::with_name("destination")
-                .help("Destination path to which processed config will be written. Defaults to overwriting target.")
-                .required(false)
-                .value_name("destination")
+            clap::Arg::with_name("autoexec")
+                .help("How later autoexec commands are merged with those of earlier configs.")
+                .long("autoexec")
+                .short("a")
+                .takes_value(true)
+                .possible_values(&["append", "prepend", "replace", "dedupe"])
+                .value_name("mode")
+        )
+        .arg(
+            clap::Arg::with_name("mount-base")
+                .help("Directory against which relative mount paths of the target are resolved.")
+                .long("mount-base")
+                .short("m")
+                .takes_value(true)
+                .value_name("dir")
+        )
+        .subcommand(
+            clap::SubCommand::with_name("validate")
+                .about("Validates config files against the standard DOSBox settings.")
+                .arg(configs_arg.clone().help("Config files to be validated."))
+        )
+        .subcommand(
+            clap::SubCommand::with_name("get")
+                .about("Prints a setting of the merged configs.")
+                .arg(
+                    clap::Arg::with_name("setting")
+                        .help("Setting to print, e.g. 'cpu.core'.")
+                        .required(true)
+                        .value_name("section.key")
+                )
+                .arg(configs_arg.clone().help("Config files to be merged in order."))
+        )
+        .subcommand(
+            clap::SubCommand::with_name("set")
+                .about("Sets a setting of a config.")
+                .arg(
+                    clap::Arg::with_name("assignment")
+                        .help("Setting to set, e.g. 'cpu.core=dynamic'.")
+                        .required(true)
+                        .value_name("section.key=value")
+                )
+                .arg(
+                    clap::Arg::with_name("config")
+                        .help("Config file to be edited.")
+                        .required(true)
+                        .value_name("config")
+                )
+        )
+        .subcommand(
+            clap::SubCommand::with_name("unset")
+                .about("Removes a setting from a config.")
+                .arg(
+                    clap::Arg::with_name("setting")
+                        .help("Setting to remove, e.g. 'cpu.core'.")
+                        .required(true)
+                        .value_name("section.key")
+                )
+                .arg(
+                    clap::Arg::with_name("config")
+                        .help("Config file to be edited.")
+                        .required(true)
+                        .value_name("config")
+                )
         )
         .get_matches();
 
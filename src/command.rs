@@ -2,17 +2,49 @@ use std::collections::HashMap;
 use std::error::Error;
 use std::process;
 
+use config::Environment;
 use mappings::Mappings;
+use schemas;
+use template::{Template, TemplateError};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A command argument, either a plain value or a group of values that is only
+/// included when the mapping named by `when` is set and not empty, e.g.
+/// `{"when": "conf", "args": ["-conf", "{conf}"]}`.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(untagged)]
+pub enum Argument {
+    #[schemars(schema_with = "schemas::template")]
+    Value(String),
+    Conditional {
+        when: String,
+
+        #[schemars(schema_with = "schemas::template_array")]
+        args: Vec<String>,
+    },
+}
+
+impl Argument {
+    pub fn values(&self) -> &[String] {
+        match *self {
+            Argument::Value(ref val) => ::std::slice::from_ref(val),
+            Argument::Conditional { ref args, .. } => args,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct Command {
+    #[schemars(schema_with = "schemas::template")]
     pub cmd: String,
-    pub args: Vec<String>,
+    pub args: Vec<Argument>,
 
     #[serde(default)]
+    #[schemars(schema_with = "schemas::template_map")]
     pub evars: HashMap<String, String>,
 
     #[serde(default = "Command::wd_default")]
+    #[schemars(schema_with = "schemas::template")]
     pub wd: String,
 }
 
@@ -21,15 +53,73 @@ impl Command {
         String::from("{target}")
     }
 
-    pub fn apply_mappings(&mut self, mappings: &Mappings) {
+    /// Checks the syntax of every template in the command.
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        Template::new(&self.cmd).validate()?;
+        Template::new(&self.wd).validate()?;
+
+        for val in self.args.iter().flat_map(Argument::values) {
+            Template::new(val).validate()?;
+        }
+
+        for val in self.evars.values() {
+            Template::new(val).validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Expands environment variables in every field of the command.
+    pub fn apply_environment(&mut self, environment: &Environment) -> Result<(), Box<Error>> {
+        self.cmd = environment.resolve(&self.cmd)?;
+        self.wd = environment.resolve(&self.wd)?;
+
+        for val in self.evars.values_mut() {
+            *val = environment.resolve(val)?;
+        }
+
+        for arg in &mut self.args {
+            match *arg {
+                Argument::Value(ref mut val) => *val = environment.resolve(val)?,
+                Argument::Conditional { ref mut args, .. } => {
+                    for val in args.iter_mut() {
+                        *val = environment.resolve(val)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn apply_mappings(&mut self, mappings: &Mappings) -> Result<(), Box<Error>> {
         mappings.replace(&mut self.wd);
-        mappings.replace_vec(&mut self.args);
         mappings.replace_map(&mut self.evars);
+
+        let mut args = Vec::new();
+
+        for arg in &self.args {
+            if let Argument::Conditional { ref when, .. } = *arg {
+                if !mappings.is_set(when) {
+                    continue;
+                }
+            }
+
+            for val in arg.values() {
+                args.extend(mappings.expand(val)?.into_iter().map(Argument::Value));
+            }
+        }
+
+        self.args = args;
+
+        Ok(())
     }
 
     pub fn execute(&self) -> Result<(), Box<Error>> {
         let mut cmd = process::Command::new(&self.cmd);
-        cmd.args(&self.args).envs(&self.evars).current_dir(&self.wd);
+        cmd.args(self.args.iter().flat_map(Argument::values))
+            .envs(&self.evars)
+            .current_dir(&self.wd);
 
         println!("Executing command => {:#?}: {:?}", self.wd, cmd);
 
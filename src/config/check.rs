@@ -0,0 +1,436 @@
+extern crate regex;
+extern crate serde_json;
+
+use config::origin::{Origin, Origins};
+use config::persisted;
+use extractor::Extractor;
+use mappings::Mappings;
+use profile::Profile;
+use template::Template;
+
+use regex::Regex;
+use serde_json::Value;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+const TOP_LEVEL_KEYS: &[&str] = &["$schema", "extractors", "profiles"];
+const EXTRACTOR_KEYS: &[&str] = &["extensions", "command"];
+const PROFILE_KEYS: &[&str] = &["command", "executables", "features", "dosbox"];
+const COMMAND_KEYS: &[&str] = &["cmd", "args", "evars", "wd"];
+const CONDITIONAL_KEYS: &[&str] = &["when", "args"];
+const FEATURE_KEYS: &[&str] = &["pattern", "weight"];
+const DOSBOX_KEYS: &[&str] = &["base", "gog", "mount_base", "dialect", "config", "overrides", "output"];
+
+/// A problem found in the configuration, located by the file and line it was
+/// most likely set at and the JSON path of the offending value. Warnings point
+/// out likely mistakes in otherwise valid configs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub file: String,
+    pub line: Option<usize>,
+    pub path: String,
+    pub message: String,
+    pub warning: bool,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}: ", self.file, line, self.path)?,
+            None => write!(f, "{}: {}: ", self.file, self.path)?,
+        }
+
+        if self.warning {
+            write!(f, "warning: ")?;
+        }
+
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Merges config files and drop-ins the same way they are loaded, and checks
+/// the result for invalid regexes, unknown keys, unknown placeholders,
+/// profiles without executables, extractors sharing extensions and commands
+/// that can't be found.
+pub fn check<U>(paths: &[String], dir: Option<&U>) -> Result<Vec<Problem>, Box<Error>>
+where
+    U: AsRef<Path>,
+{
+    let mut texts = Vec::new();
+
+    for path in paths {
+        texts.push((path.clone(), persisted::read_text(path)?));
+    }
+
+    check_texts(&texts, dir)
+}
+
+/// Checks in-memory config files as `check` does, each given as the name it
+/// is reported under, whose extension gives its format, along with its
+/// contents.
+pub fn check_texts<U>(texts: &[(String, String)], dir: Option<&U>) -> Result<Vec<Problem>, Box<Error>>
+where
+    U: AsRef<Path>,
+{
+    let (merged, origins) = persisted::merge_texts(texts, dir)?;
+
+    let mut checker = Checker {
+        paths: texts.iter().map(|&(ref path, _)| path.clone()).collect(),
+        origins: &origins,
+        texts: texts.iter().cloned().collect(),
+        problems: Vec::new(),
+    };
+    checker.check(&merged);
+
+    Ok(checker.problems)
+}
+
+struct Checker<'a> {
+    paths: Vec<String>,
+    origins: &'a Origins,
+    texts: HashMap<String, String>,
+    problems: Vec<Problem>,
+}
+
+impl<'a> Checker<'a> {
+    fn check(&mut self, merged: &Value) {
+        self.check_keys("", None, merged, TOP_LEVEL_KEYS);
+
+        for &(section, kind) in &[("extractors", "extractor"), ("profiles", "profile")] {
+            let entries = match merged.get(section) {
+                Some(&Value::Object(ref entries)) => entries,
+                Some(_) => {
+                    self.report(section, None, section, String::from("expected an object"));
+                    continue;
+                }
+                None => continue,
+            };
+
+            let mut names: Vec<&String> = entries.keys().collect();
+            names.sort();
+
+            for name in names {
+                let key = format!("{}.{}", section, name);
+                let reported = self.problems.len();
+
+                if kind == "extractor" {
+                    self.check_extractor(&key, &entries[name]);
+                } else {
+                    self.check_profile(&key, &entries[name]);
+                }
+
+                // Type errors are only worth reporting once nothing more
+                // specific was found.
+                if self.problems.len() == reported {
+                    let err = if kind == "extractor" {
+                        serde_json::from_value::<Extractor>(entries[name].clone()).err()
+                    } else {
+                        serde_json::from_value::<Profile>(entries[name].clone()).err()
+                    };
+
+                    if let Some(err) = err {
+                        self.report(&key, Some(&key), name, err.to_string());
+                    }
+                }
+            }
+        }
+
+        self.check_extensions(merged);
+    }
+
+    fn check_extractor(&mut self, entry: &str, extractor: &Value) {
+        self.check_keys(entry, Some(entry), extractor, EXTRACTOR_KEYS);
+
+        if let Some(command) = extractor.get("command") {
+            let path = format!("{}.command", entry);
+            self.check_command(&path, entry, command, &HashSet::new());
+        }
+    }
+
+    fn check_profile(&mut self, entry: &str, profile: &Value) {
+        self.check_keys(entry, Some(entry), profile, PROFILE_KEYS);
+
+        let mut mappings: HashSet<String> = ["executable", "executable_dir"]
+            .iter()
+            .map(|key| String::from(*key))
+            .collect();
+
+        let executables = profile.get("executables").and_then(Value::as_array);
+        if executables.map_or(true, |executables| executables.is_empty()) {
+            let name = &entry["profiles.".len()..];
+            let message = String::from("profile has no executables, so there is nothing to run");
+            self.report(entry, Some(entry), name, message);
+        }
+
+        for (i, executable) in executables.into_iter().flat_map(|e| e).enumerate() {
+            let path = format!("{}.executables[{}]", entry, i);
+            self.check_regex(&path, entry, executable, &mut mappings);
+        }
+
+        let features = profile.get("features").and_then(Value::as_array);
+        for (i, feature) in features.into_iter().flat_map(|f| f).enumerate() {
+            let path = format!("{}.features[{}]", entry, i);
+            self.check_keys(&path, Some(entry), feature, FEATURE_KEYS);
+
+            if let Some(pattern) = feature.get("pattern") {
+                let path = format!("{}.pattern", path);
+                self.check_regex(&path, entry, pattern, &mut mappings);
+            }
+        }
+
+        if let Some(dosbox) = profile.get("dosbox") {
+            let path = format!("{}.dosbox", entry);
+            self.check_keys(&path, Some(entry), dosbox, DOSBOX_KEYS);
+            mappings.insert(String::from("dosbox_conf"));
+
+            for key in &["base", "mount_base", "overrides"] {
+                if let Some(&Value::String(ref template)) = dosbox.get(key) {
+                    let path = format!("{}.{}", path, key);
+                    self.check_template(&path, entry, template, &mappings);
+                }
+            }
+        }
+
+        if let Some(command) = profile.get("command") {
+            let path = format!("{}.command", entry);
+            self.check_command(&path, entry, command, &mappings);
+        }
+    }
+
+    fn check_command(&mut self, path: &str, entry: &str, command: &Value, mappings: &HashSet<String>) {
+        self.check_keys(path, Some(entry), command, COMMAND_KEYS);
+
+        if let Some(&Value::String(ref cmd)) = command.get("cmd") {
+            let path = format!("{}.cmd", path);
+            self.check_template(&path, entry, cmd, mappings);
+
+            if !is_executable(cmd) {
+                let message = format!("'{}' was not found on PATH", cmd);
+                self.report(&path, Some(entry), cmd, message);
+            }
+        }
+
+        if let Some(&Value::String(ref wd)) = command.get("wd") {
+            let path = format!("{}.wd", path);
+            self.check_template(&path, entry, wd, mappings);
+        }
+
+        let args = command.get("args").and_then(Value::as_array);
+        for (i, arg) in args.into_iter().flat_map(|a| a).enumerate() {
+            let path = format!("{}.args[{}]", path, i);
+
+            match *arg {
+                Value::String(ref arg) => self.check_template(&path, entry, arg, mappings),
+                Value::Object(_) => {
+                    self.check_keys(&path, Some(entry), arg, CONDITIONAL_KEYS);
+
+                    if let Some(&Value::String(ref when)) = arg.get("when") {
+                        if !is_mapping(when, mappings) {
+                            let message = format!("unknown placeholder '{}'", when);
+                            self.report(&format!("{}.when", path), Some(entry), when, message);
+                        }
+                    }
+
+                    let args = arg.get("args").and_then(Value::as_array);
+                    for (j, arg) in args.into_iter().flat_map(|a| a).enumerate() {
+                        if let Value::String(ref arg) = *arg {
+                            let path = format!("{}.args[{}]", path, j);
+                            self.check_template(&path, entry, arg, mappings);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let evars = command.get("evars").and_then(Value::as_object);
+        for (key, val) in evars.into_iter().flat_map(|e| e) {
+            if let Value::String(ref val) = *val {
+                let path = format!("{}.evars.{}", path, key);
+                self.check_template(&path, entry, val, mappings);
+            }
+        }
+    }
+
+    /// Reports every extension handled by more than one extractor, on every
+    /// extractor but the first.
+    fn check_extensions(&mut self, merged: &Value) {
+        let mut handlers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        let extractors = merged.get("extractors").and_then(Value::as_object);
+        for (name, extractor) in extractors.into_iter().flat_map(|e| e) {
+            let extensions = extractor.get("extensions").and_then(Value::as_array);
+            for extension in extensions.into_iter().flat_map(|e| e).filter_map(Value::as_str) {
+                handlers
+                    .entry(String::from(extension))
+                    .or_insert_with(Vec::new)
+                    .push(name.clone());
+            }
+        }
+
+        for (extension, mut names) in handlers {
+            names.sort();
+
+            for name in &names[1..] {
+                let entry = format!("extractors.{}", name);
+                let message = format!("extension '{}' is also handled by extractor '{}'", extension, names[0]);
+                self.report(&format!("{}.extensions", entry), Some(&entry), &extension, message);
+            }
+        }
+    }
+
+    fn check_keys(&mut self, path: &str, entry: Option<&str>, value: &Value, known: &[&str]) {
+        let object = match *value {
+            Value::Object(ref object) => object,
+            _ => return,
+        };
+
+        for key in object.keys().filter(|key| !known.contains(&key.as_str())) {
+            let path = if path.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", path, key)
+            };
+            let message = format!("unknown key '{}', expected one of {}", key, known.join(", "));
+            self.report(&path, entry, key, message);
+        }
+    }
+
+    /// Checks that a value is a valid regex, adding its named capture groups
+    /// to the mappings.
+    fn check_regex(&mut self, path: &str, entry: &str, value: &Value, mappings: &mut HashSet<String>) {
+        let pattern = match *value {
+            Value::String(ref pattern) => pattern,
+            _ => return,
+        };
+
+        match Regex::new(pattern) {
+            Ok(regex) => mappings.extend(regex.capture_names().filter_map(|n| n).map(String::from)),
+            Err(err) => {
+                // The last line of a parse error holds the message, the rest
+                // points at the pattern.
+                let err = err.to_string();
+                let reason = err.lines().last().unwrap_or_default().trim().trim_start_matches("error: ");
+                let message = format!("invalid regex: {}", reason);
+                self.report(path, Some(entry), pattern, message);
+            }
+        }
+    }
+
+    fn check_template(&mut self, path: &str, entry: &str, template: &str, mappings: &HashSet<String>) {
+        if let Err(err) = Template::new(template).validate() {
+            self.report(path, Some(entry), template, err.to_string());
+            return;
+        }
+
+        // Environment variables are resolved before the mappings.
+        let variables = Regex::new(r"\$\{[^}]*\}").unwrap();
+        let without_variables = variables.replace_all(template, "");
+
+        for key in Template::new(&without_variables).keys() {
+            if !is_mapping(key, mappings) {
+                let message = format!("unknown placeholder '{{{}}}'", key);
+                self.report(path, Some(entry), template, message);
+            } else if key == "archives" {
+                let message = String::from(
+                    "{archives} joins the archives with spaces, which splits paths containing \
+                     spaces, use {archive_N} instead",
+                );
+                self.warn(path, Some(entry), template, message);
+            }
+        }
+    }
+
+    /// Records a problem, locating it at the first line holding `needle` in
+    /// the files the entry came from, starting at the entry and trying the
+    /// last file first.
+    fn report(&mut self, path: &str, entry: Option<&str>, needle: &str, message: String) {
+        let candidates: Vec<(String, usize)> = match entry.and_then(|entry| self.origins.get(entry)) {
+            Some(origins) => origins
+                .iter()
+                .rev()
+                .filter_map(|origin| match *origin {
+                    Origin::File { ref path, line } => Some((path.clone(), line.unwrap_or(1))),
+                    _ => None,
+                })
+                .collect(),
+            None => self.paths.iter().rev().map(|path| (path.clone(), 1)).collect(),
+        };
+
+        let quoted = [
+            serde_json::to_string(needle).unwrap_or_default(),
+            format!("'{}'", needle),
+            String::from(needle),
+        ];
+
+        let mut location = None;
+        'search: for form in &quoted {
+            for &(ref file, start) in &candidates {
+                let text = self.text(file);
+                let found = text
+                    .lines()
+                    .enumerate()
+                    .skip(start - 1)
+                    .find(|&(_, line)| line.contains(form.as_str()));
+
+                if let Some((index, _)) = found {
+                    location = Some((file.clone(), Some(index + 1)));
+                    break 'search;
+                }
+            }
+        }
+
+        let (file, line) = location.unwrap_or_else(|| match candidates.first() {
+            Some(&(ref file, start)) => (file.clone(), Some(start)),
+            None => (String::from("<config>"), None),
+        });
+
+        self.problems.push(Problem {
+            file: file,
+            line: line,
+            path: String::from(path),
+            message: message,
+            warning: false,
+        });
+    }
+
+    /// Records a warning, located as `report` locates problems.
+    fn warn(&mut self, path: &str, entry: Option<&str>, needle: &str, message: String) {
+        self.report(path, entry, needle, message);
+
+        if let Some(problem) = self.problems.last_mut() {
+            problem.warning = true;
+        }
+    }
+
+    fn text(&mut self, file: &str) -> &str {
+        self.texts
+            .entry(String::from(file))
+            .or_insert_with(|| persisted::read_text(&file).unwrap_or_default())
+    }
+}
+
+fn is_mapping(key: &str, mappings: &HashSet<String>) -> bool {
+    Mappings::is_standard(key) || mappings.contains(key)
+}
+
+/// Whether a command can be found, either as a path or on `PATH`. Commands
+/// built from placeholders or variables, and relative paths, which depend on
+/// the working directory, are given the benefit of the doubt.
+fn is_executable(cmd: &str) -> bool {
+    if cmd.contains(|c| c == '{' || c == '$' || c == '~') {
+        return true;
+    }
+
+    if cmd.contains('/') {
+        return !Path::new(cmd).is_absolute() || Path::new(cmd).is_file();
+    }
+
+    env::var_os("PATH").map_or(false, |paths| {
+        env::split_paths(&paths).any(|dir| dir.join(cmd).is_file())
+    })
+}
@@ -1,39 +1,94 @@
 extern crate clap;
 
+use super::origin::Origin;
 use super::source::Source;
+use super::subcommand::ConfigCommand;
+
+use std::ffi::OsString;
 
 pub struct CommandLine {
     pub archives: Option<Vec<String>>,
     pub config: Option<String>,
+    pub config_dir: Option<String>,
     pub data_dir: Option<String>,
     pub temp_dir: Option<String>,
     pub target_dir: Option<String>,
     pub extractor: Option<String>,
     pub profile: Option<String>,
+    pub dry_run: Option<bool>,
+    pub command: Option<ConfigCommand>,
 }
 
 impl CommandLine {
+    /// Parses the arguments of the process, exiting with a message when they
+    /// are invalid or help was asked for.
     pub fn current() -> CommandLine {
-        let commandline = CommandLine::read();
+        CommandLine::from_matches(&CommandLine::app().get_matches())
+    }
 
+    /// Parses the given arguments, which exclude the name of the program.
+    pub fn from_args<I, T>(args: I) -> Result<CommandLine, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args = Some(OsString::from("rxr"))
+            .into_iter()
+            .chain(args.into_iter().map(Into::into));
+
+        Ok(CommandLine::from_matches(&CommandLine::app().get_matches_from_safe(args)?))
+    }
+
+    fn from_matches(commandline: &clap::ArgMatches) -> CommandLine {
         CommandLine {
-            archives: Some(
-                commandline
-                    .values_of("archives")
-                    .unwrap()
-                    .map(String::from)
-                    .collect(),
-            ),
+            archives: commandline
+                .values_of("archives")
+                .or_else(|| {
+                    commandline
+                        .subcommand_matches("config")
+                        .and_then(|config| config.subcommand_matches("show"))
+                        .and_then(|show| show.values_of("archives"))
+                })
+                .map(|archives| archives.map(String::from).collect()),
             config: commandline.value_of("config").map(String::from),
+            config_dir: commandline.value_of("config_dir").map(String::from),
             data_dir: commandline.value_of("data_dir").map(String::from),
             temp_dir: commandline.value_of("temp_dir").map(String::from),
             target_dir: commandline.value_of("target_dir").map(String::from),
             extractor: commandline.value_of("extractor").map(String::from),
             profile: commandline.value_of("profile").map(String::from),
+            dry_run: if commandline.is_present("dry_run") {
+                Some(true)
+            } else {
+                None
+            },
+            command: CommandLine::config_command(commandline),
         }
     }
 
-    pub fn read() -> clap::ArgMatches<'static> {
+    fn config_command(commandline: &clap::ArgMatches) -> Option<ConfigCommand> {
+        match commandline.subcommand() {
+            ("config", Some(config)) => match config.subcommand() {
+                ("convert", Some(args)) => Some(ConfigCommand::Convert {
+                    input: String::from(args.value_of("input").unwrap()),
+                    output: args.value_of("output").map(String::from),
+                    format: args.value_of("to").and_then(|format| format.parse().ok()),
+                }),
+                ("show", Some(_)) => Some(ConfigCommand::Show),
+                ("check", Some(_)) => Some(ConfigCommand::Check),
+                ("schema", Some(_)) => Some(ConfigCommand::Schema),
+                ("init", Some(args)) => Some(ConfigCommand::Init {
+                    output: args.value_of("output").map(String::from),
+                    format: args.value_of("to").and_then(|format| format.parse().ok()),
+                    force: args.is_present("force"),
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn app() -> clap::App<'static, 'static> {
         clap::App::new("rxr - Rust eXtract and Run")
         .version(env!("CARGO_PKG_VERSION"))
         .author("Stefan Alberts <stefan6573@gmail.com>")
@@ -43,14 +98,14 @@ impl CommandLine {
                 .short("c")
                 .long("config")
                 .value_name("config")
-                .help("Configuration file to use.")
+                .help("Configuration file to use, otherwise RXR_CONFIG.")
                 .takes_value(true),
         )
         .arg(
             clap::Arg::with_name("config_dir")
                 .long("config-directory")
                 .value_name("configuration directory")
-                .help("Configuration directory to use.")
+                .help("Configuration directory to use, holding the configuration file (rxr.json, rxr.toml or rxr.yaml) along with profiles.d and extractors.d directories of one profile or extractor per file, otherwise RXR_CONFIG_DIR.")
                 .takes_value(true),
         )
         .arg(
@@ -58,7 +113,7 @@ impl CommandLine {
                 .short("d")
                 .long("data-directory")
                 .value_name("data directory")
-                .help("Data directory to use, otherwise environment variables RXR_DATA_DIR or XDG_DATA_HOME will be used, lastly falling back to the config file entry (paths.data).")
+                .help("Data directory to use, otherwise RXR_DATA_DIR, lastly falling back to $XDG_DATA_HOME/rxr or ~/.local/share/rxr.")
                 .takes_value(true),
         )
         .arg(
@@ -66,7 +121,7 @@ impl CommandLine {
                 .short("o")
                 .long("target-directory")
                 .value_name("target directory")
-                .help("Target directory to which archive will be extracted, archive will otherwise be extracted to {tempory-directory}/{archive name}")
+                .help("Target directory to which archive will be extracted, otherwise RXR_TARGET_DIR, archive will otherwise be extracted to {tempory-directory}/{archive name}")
                 .takes_value(true),
         )
         .arg(
@@ -74,7 +129,7 @@ impl CommandLine {
                 .short("t")
                 .long("temporary-directory")
                 .value_name("temp")
-                .help("Temp directory to use, otherwise environment variables RXR_TEMP_DIR or XDG_DATA_HOME will be used, lastly falling back to the config file entry (paths.temp).")
+                .help("Temp directory to use, otherwise RXR_TEMP_DIR, lastly falling back to $XDG_CACHE_HOME/rxr or ~/.cache/rxr.")
                 .takes_value(true),
         )
         .arg(
@@ -82,7 +137,7 @@ impl CommandLine {
                 .short("x")
                 .long("extractor")
                 .value_name("extractor")
-                .help("Extractor to use, otherwise the executor that has the highest match based on configured heuristics.")
+                .help("Extractor to use, otherwise RXR_EXTRACTOR or the executor that has the highest match based on configured heuristics.")
                 .takes_value(true),
         )
         .arg(
@@ -90,17 +145,90 @@ impl CommandLine {
                 .short("p")
                 .long("profile")
                 .value_name("profile")
-                .help("Profile/Executor to use, otherwise the profile that has the highest match based on configured heuristics.")
+                .help("Profile/Executor to use, otherwise RXR_PROFILE or the profile that has the highest match based on configured heuristics.")
                 .takes_value(true),
         )
+        .arg(
+            clap::Arg::with_name("dry_run")
+                .short("n")
+                .long("dry-run")
+                .help("Prints the available mappings and the resolved commands without running them, also enabled by RXR_DRY_RUN=1."),
+        )
         .arg(
             clap::Arg::with_name("archives")
-                .help("Archives to be extracted.")
-                .required(true)
+                .help("Archives to be extracted, otherwise RXR_ARCHIVES, separated by ':' as in PATH.")
                 .value_name("archives")
                 .multiple(true),
         )
-        .get_matches()
+        .subcommand(
+            clap::SubCommand::with_name("config")
+                .about("Works with configuration files.")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    clap::SubCommand::with_name("convert")
+                        .about("Converts a configuration file between JSON, TOML and YAML.")
+                        .arg(
+                            clap::Arg::with_name("input")
+                                .help("Configuration file to convert, in the format given by its extension.")
+                                .required(true)
+                                .value_name("input"),
+                        )
+                        .arg(
+                            clap::Arg::with_name("output")
+                                .help("File to write the converted configuration to, otherwise it is printed.")
+                                .value_name("output"),
+                        )
+                        .arg(
+                            clap::Arg::with_name("to")
+                                .long("to")
+                                .value_name("format")
+                                .help("Format to convert to, otherwise the one given by the extension of the output file.")
+                                .possible_values(&["json", "toml", "yaml"])
+                                .takes_value(true),
+                        ),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("show")
+                        .about("Shows the effective configuration and where every value came from.")
+                        .arg(
+                            clap::Arg::with_name("archives")
+                                .help("Archives to show the configuration for.")
+                                .value_name("archives")
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("check")
+                        .about("Checks the configuration files for invalid regexes, unknown keys and placeholders, profiles without executables, extractors sharing extensions and commands missing from PATH."),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("init")
+                        .about("Writes the built-in configuration to a file for editing.")
+                        .arg(
+                            clap::Arg::with_name("output")
+                                .help("File to write the configuration to, otherwise $XDG_CONFIG_HOME/rxr/rxr.json or ~/.config/rxr/rxr.json.")
+                                .value_name("output"),
+                        )
+                        .arg(
+                            clap::Arg::with_name("to")
+                                .long("to")
+                                .value_name("format")
+                                .help("Format to write, otherwise the one given by the extension of the output file.")
+                                .possible_values(&["json", "toml", "yaml"])
+                                .takes_value(true),
+                        )
+                        .arg(
+                            clap::Arg::with_name("force")
+                                .short("f")
+                                .long("force")
+                                .help("Overwrites the output file if it already exists."),
+                        ),
+                )
+                .subcommand(
+                    clap::SubCommand::with_name("schema")
+                        .about("Prints the JSON Schema of the configuration file, for editors to validate and complete it with."),
+                ),
+        )
     }
 }
 
@@ -114,7 +242,22 @@ impl From<CommandLine> for Source {
             target_dir: commandline.target_dir,
             extractor: commandline.extractor,
             profile: commandline.profile,
+            dry_run: commandline.dry_run,
             ..Default::default()
-        }
+        }.with_origins(|field| {
+            let flag = match field {
+                "archives" => "archives",
+                "config" => "--config",
+                "data_dir" => "--data-directory",
+                "temp_dir" => "--temporary-directory",
+                "target_dir" => "--target-directory",
+                "extractor" => "--extractor",
+                "profile" => "--profile",
+                "dry_run" => "--dry-run",
+                _ => return None,
+            };
+
+            Some(Origin::CommandLine(flag))
+        })
     }
 }
@@ -1,7 +1,16 @@
+use super::origin::Origin;
 use super::source::Source;
 
+/// Defaults set when rxr was compiled, which every layer but the XDG
+/// directories takes precedence over.
+#[derive(Debug, Clone, Default)]
 pub struct Compiled {
     pub config: Option<String>,
+    /// System-wide config, layered beneath the user's config.
+    pub system_config: Option<String>,
+    /// Directories searched for configs when `$XDG_CONFIG_DIRS` is unset,
+    /// `/etc/xdg` as the specification asks.
+    pub xdg_config_dirs: Option<String>,
     pub data_dir: Option<String>,
     pub temp_dir: Option<String>,
 }
@@ -10,6 +19,10 @@ impl Compiled {
     pub fn new() -> Compiled {
         Compiled {
             config: option_env!("RXR_CONFIG").map(String::from),
+            system_config: Some(String::from(
+                option_env!("RXR_SYSTEM_CONFIG").unwrap_or("/etc/rxr/rxr.json"),
+            )),
+            xdg_config_dirs: Some(String::from("/etc/xdg")),
             data_dir: option_env!("RXR_DATA_DIR").map(String::from),
             temp_dir: option_env!("RXR_TEMP_DIR").map(String::from),
         }
@@ -23,6 +36,11 @@ impl From<Compiled> for Source {
             data_dir: compiled.data_dir,
             temp_dir: compiled.temp_dir,
             ..Default::default()
-        }
+        }.with_origins(|field| match field {
+            "config" => Some(Origin::Compiled("RXR_CONFIG")),
+            "data_dir" => Some(Origin::Compiled("RXR_DATA_DIR")),
+            "temp_dir" => Some(Origin::Compiled("RXR_TEMP_DIR")),
+            _ => None,
+        })
     }
 }
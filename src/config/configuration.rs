@@ -1,15 +1,19 @@
 use config::commandline::CommandLine;
 use config::compiled::Compiled;
-use config::error::ConfigError;
+use config::origin::{Origin, Origins};
 use config::environment::Environment;
-use config::persisted::Persisted;
+use config::persisted::{self, Persisted};
+use config::sidecar::Sidecar;
 use config::source::Source;
 
 use extractor::Extractor;
+use mappings::Mappings;
 use profile::Profile;
 
 use std::collections::HashMap;
+use std::env;
 use std::error::Error;
+use std::path::Path;
 
 #[derive(Deserialize, Debug)]
 pub struct Configuration {
@@ -20,35 +24,230 @@ pub struct Configuration {
     pub target_dir: String,
     pub extractor: Option<String>,
     pub profile: Option<String>,
+    pub dry_run: bool,
+    pub environment: Environment,
     pub extractors: HashMap<String, Extractor>,
     pub profiles: HashMap<String, Profile>,
+
+    /// The overrides of the sidecar files of the first archive, whose
+    /// profile is already merged into `profile`.
+    #[serde(skip)]
+    pub sidecar: Sidecar,
+
+    /// Where every value came from, see `Source::origins`.
+    #[serde(skip)]
+    pub origins: Origins,
+}
+
+/// Builds a `Configuration` from explicit layers instead of the process:
+/// command line arguments, environment variables, config files given in
+/// memory and compile-time defaults. Every layer starts out empty, and only
+/// the files and directories the layers name are read from disk.
+#[derive(Debug, Default)]
+pub struct ConfigurationBuilder {
+    args: Vec<String>,
+    environment: Environment,
+    persisted: Vec<(String, String)>,
+    compiled: Compiled,
+}
+
+impl ConfigurationBuilder {
+    /// Command line arguments, without the name of the program.
+    pub fn args<I, T>(mut self, args: I) -> ConfigurationBuilder
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn environment<T>(mut self, environment: T) -> ConfigurationBuilder
+    where
+        T: Into<Environment>,
+    {
+        self.environment = environment.into();
+        self
+    }
+
+    /// Adds a config file given in memory, layered over the previous ones,
+    /// under a name whose extension gives its format. Once any is given,
+    /// config files are no longer looked up on disk, though the drop-ins of a
+    /// config directory still are.
+    pub fn persisted<T, U>(mut self, name: T, text: U) -> ConfigurationBuilder
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        self.persisted.push((name.into(), text.into()));
+        self
+    }
+
+    pub fn compiled(mut self, compiled: Compiled) -> ConfigurationBuilder {
+        self.compiled = compiled;
+        self
+    }
+
+    /// Builds the configuration, with the sidecar files of the first archive
+    /// layered over it once the archive and target directory are known.
+    pub fn build(self) -> Result<Configuration, Box<Error>> {
+        self.merge()?.validate()?.with_sidecars()
+    }
+
+    /// Merges every layer but the sidecar files, in order of precedence: the
+    /// command line, the environment, the config files, the compile-time
+    /// defaults and lastly the XDG base directories.
+    fn merge(&self) -> Result<Source, Box<Error>> {
+        let args = CommandLine::from_args(&self.args)?;
+        let environment = &self.environment;
+        let compiled = &self.compiled;
+
+        let config_dir = Configuration::config_dir(&args, environment, compiled);
+        let persisted = if self.persisted.is_empty() {
+            Persisted::read_layers(
+                &Configuration::layers(&args, environment, compiled),
+                config_dir.as_ref(),
+            )?
+        } else {
+            Persisted::read_texts(&self.persisted, config_dir.as_ref())?
+        };
+        let dir_config = args.config_dir.as_ref().map(Configuration::dir_config);
+
+        Ok(Source::from(args)
+            .merge(
+                Source {
+                    config: dir_config,
+                    ..Default::default()
+                }.with_origins(|_| Some(Origin::CommandLine("--config-directory"))),
+            )
+            .merge(environment.source()?)
+            .merge(Source::from(persisted))
+            .merge(Source::from(compiled.clone()))
+            .merge(environment.xdg_source(compiled)))
+    }
 }
 
 impl Configuration {
+    pub fn builder() -> ConfigurationBuilder {
+        ConfigurationBuilder::default()
+    }
+
+    /// Layers the sidecar files of the first archive that exist over the
+    /// configuration, replacing those layered before. Their profile takes
+    /// precedence over every other layer but the command line.
+    pub fn with_sidecars(mut self) -> Result<Configuration, Box<Error>> {
+        let paths = match self.archives.first() {
+            Some(archive) => Sidecar::find(archive, &self.target_dir),
+            None => Vec::new(),
+        };
+
+        if paths.is_empty() {
+            return Ok(self);
+        }
+
+        let source = Sidecar::source(&paths)?;
+        source.validate_templates()?;
+
+        let from_command_line = match self.origins.get("profile").and_then(|origins| origins.last()) {
+            Some(&Origin::CommandLine(_)) => true,
+            _ => false,
+        };
+
+        if !from_command_line && source.profile.is_some() {
+            self.profile = source.profile;
+            self.origins.insert(String::from("profile"), source.origins["profile"].clone());
+        }
+
+        self.sidecar = source.sidecar.unwrap_or_default();
+        self.origins.insert(String::from("sidecar"), source.origins["sidecar"].clone());
+
+        Ok(self)
+    }
+
+    /// Loads the configuration of the process, from its arguments, its
+    /// environment and the compile-time defaults.
     pub fn load() -> Result<Configuration, Box<Error>> {
-        let args = CommandLine::current();
-        let environment = Environment::current();
-        let persisted: Persisted;
-        let compiled = Compiled::new();
-
-        {
-            let persisted_path = args.config
-                .as_ref()
-                .or_else(|| environment.get_config())
-                .or_else(|| compiled.config.as_ref());
-
-            if let Some(path) = persisted_path {
-                persisted = Persisted::read(path)?;
-            } else {
-                return Err(Box::new(ConfigError::NoConfig));
-            }
+        Configuration::builder()
+            .args(env::args().skip(1))
+            .environment(Environment::current())
+            .compiled(Compiled::new())
+            .build()
+    }
+
+    /// Returns the config files to read, in order: the system-wide config
+    /// when it exists, followed by the first of `--config`, the config
+    /// directory's file, `$RXR_CONFIG`, `$RXR_CONFIG_DIR`'s file, the
+    /// compile-time config or the first config in the XDG config
+    /// directories. The built-in config is read when there are none.
+    pub fn layers(args: &CommandLine, environment: &Environment, compiled: &Compiled) -> Vec<String> {
+        let args_dir_config = args.config_dir.as_ref().map(Configuration::dir_config);
+        let env_dir_config = environment.get_config_dir().map(Configuration::dir_config);
+        let persisted_path = args.config
+            .as_ref()
+            .or_else(|| args_dir_config.as_ref())
+            .or_else(|| environment.get_config())
+            .or_else(|| env_dir_config.as_ref())
+            .or_else(|| compiled.config.as_ref())
+            .cloned()
+            .or_else(|| environment.xdg_config(compiled).map(|(_, path)| path));
+
+        let is_dir_config = persisted_path.is_some()
+            && (persisted_path == args_dir_config || persisted_path == env_dir_config);
+        let mut layers: Vec<String> = compiled
+            .system_config
+            .iter()
+            .filter(|path| Path::new(path).exists())
+            .cloned()
+            .collect();
+
+        match persisted_path {
+            // The main file of a config directory is optional.
+            Some(ref path) if is_dir_config && !Path::new(path).exists() => {}
+            Some(path) => layers.push(path),
+            None => {}
         }
 
-        Source::from(args)
-            .merge(Source::from(environment))
-            .merge(Source::from(persisted))
-            .merge(Source::from(compiled))
-            .validate()
+        if layers.is_empty() {
+            layers.push(String::from(persisted::DEFAULT_CONFIG_PATH));
+        }
+
+        layers
+    }
+
+    /// The config directory whose drop-ins are read, from `--config-directory`
+    /// or `$RXR_CONFIG_DIR`, or else the XDG config directory the config was
+    /// found in when no config file was given.
+    pub fn config_dir(args: &CommandLine, environment: &Environment, compiled: &Compiled) -> Option<String> {
+        let given = args.config.is_some() || environment.get_config().is_some() || compiled.config.is_some();
+
+        args.config_dir
+            .as_ref()
+            .or_else(|| environment.get_config_dir())
+            .cloned()
+            .or_else(|| {
+                environment
+                    .xdg_config(compiled)
+                    .filter(|_| !given)
+                    .and_then(|(_, path)| Path::new(&path).parent().map(|dir| dir.to_string_lossy().into_owned()))
+            })
+    }
+
+    /// Finds the config file of a directory, in whichever format exists.
+    pub fn find_config<T>(dir: &T) -> Option<String>
+    where
+        T: AsRef<Path>,
+    {
+        ["rxr.json", "rxr.toml", "rxr.yaml", "rxr.yml"]
+            .iter()
+            .map(|name| dir.as_ref().join(name))
+            .find(|path| path.exists())
+            .map(|path| path.to_string_lossy().into_owned())
+    }
+
+    /// The config file of a config directory, `rxr.json` when there is none.
+    pub fn dir_config(dir: &String) -> String {
+        Configuration::find_config(dir)
+            .unwrap_or_else(|| Path::new(dir).join("rxr.json").to_string_lossy().into_owned())
     }
 
     pub fn get_extractor(&self) -> Option<&Extractor> {
@@ -69,13 +268,64 @@ impl Configuration {
         extractor
     }
 
-    pub fn get_profile(&self) -> Option<&Profile> {
+    pub fn get_profile(&self) -> Option<(&str, &Profile)> {
         if self.profile.is_some() {
             self.profiles
-                .get(self.profile.as_ref().unwrap())
-                .or_else(|| self.profiles.get("fallback"))
+                .get_key_value(self.profile.as_ref().unwrap())
+                .or_else(|| self.profiles.get_key_value("fallback"))
+                .map(|(name, profile)| (name.as_str(), profile))
         } else {
             None
         }
     }
+
+    /// Builds the standard mappings available to every extractor and profile
+    /// command: `{archive}`, `{archive_stem}`, `{archives}`, `{target}`,
+    /// `{data_dir}`, `{temp_dir}`, `{config_dir}`, `{save_dir}` and
+    /// `{profile}`, along with `{archive_N}`, `{archive_stem_N}` and
+    /// `{save_dir_N}` for every archive. `{archives}` joins the archives with
+    /// spaces, so commands taking paths that may contain spaces should use
+    /// `{archive_N}` instead.
+    pub fn mappings(&self, profile: Option<&str>) -> Mappings {
+        let mut mappings = Mappings::new();
+
+        mappings.insert("target", &self.target_dir);
+        mappings.insert("data_dir", &self.data_dir);
+        mappings.insert("temp_dir", &self.temp_dir);
+        mappings.insert(
+            "config_dir",
+            &Path::new(&self.config)
+                .parent()
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."))
+                .to_string_lossy(),
+        );
+        mappings.insert("profile", profile.unwrap_or_default());
+        mappings.insert("archives", &self.archives.join(" "));
+
+        for (i, archive) in self.archives.iter().enumerate() {
+            let stem = Path::new(archive)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let save_dir = Path::new(&self.data_dir)
+                .join("saves")
+                .join(&stem)
+                .to_string_lossy()
+                .into_owned();
+
+            if i == 0 {
+                mappings.insert("archive", archive);
+                mappings.insert("archive_stem", &stem);
+                mappings.insert("save_dir", &save_dir);
+            }
+
+            mappings.insert(&format!("archive_{}", i), archive);
+            mappings.insert(&format!("archive_stem_{}", i), &stem);
+            mappings.insert(&format!("save_dir_{}", i), &save_dir);
+        }
+
+        mappings
+    }
 }
@@ -1,83 +1,308 @@
 use super::source::Source;
 
-extern crate regex;
-use regex::Regex;
+use config::compiled::Compiled;
+use config::configuration::Configuration;
+use config::error::ConfigError;
+use config::origin::Origin;
 
 use std::collections::HashMap;
 use std::env;
+use std::path::{Path, PathBuf};
 
+/// The variable each command line option can also be given by. `RXR_DRY_RUN`
+/// takes a boolean such as `1`, `true` or `no`, and `RXR_ARCHIVES` a list
+/// separated like `PATH`. Empty variables count as unset.
+const VARIABLES: &[(&str, &str)] = &[
+    ("archives", "RXR_ARCHIVES"),
+    ("config", "RXR_CONFIG"),
+    ("config_dir", "RXR_CONFIG_DIR"),
+    ("data_dir", "RXR_DATA_DIR"),
+    ("temp_dir", "RXR_TEMP_DIR"),
+    ("target_dir", "RXR_TARGET_DIR"),
+    ("extractor", "RXR_EXTRACTOR"),
+    ("profile", "RXR_PROFILE"),
+    ("dry_run", "RXR_DRY_RUN"),
+];
+
+/// The XDG base directories rxr keeps its files under, as the variable, the
+/// default relative to `$HOME` and the field it provides.
+const XDG_DIRS: &[(&str, &str, &str)] = &[
+    ("XDG_DATA_HOME", ".local/share", "data_dir"),
+    ("XDG_CACHE_HOME", ".cache", "temp_dir"),
+];
+
+#[derive(Deserialize, Debug, Clone, Default)]
 pub struct Environment(HashMap<String, String>);
 
+impl From<HashMap<String, String>> for Environment {
+    /// Uses the given variables as they are, without expanding them.
+    fn from(variables: HashMap<String, String>) -> Environment {
+        Environment(variables)
+    }
+}
+
 impl Environment {
+    /// The variables of the process, as they are. Values are only expanded
+    /// when resolved.
     pub fn current() -> Environment {
-        Environment {
-            0: env::vars()
-                .map(|(k, v)| (k, Environment::expand(&v)))
-                .collect(),
-        }
+        Environment(env::vars().collect())
     }
 
     pub fn variable(&self, key: &str) -> Option<&String> {
         self.0.get(key)
     }
 
-    pub fn expand<T>(target: &T) -> String
+    /// Expands `$VAR`, `${VAR}` and `${VAR:-default}` from this environment
+    /// and a leading `~` to `$HOME`. `$$` produces a literal `$`, `{{...}}`
+    /// expansions are left untouched, and undefined variables without a
+    /// default are an error.
+    pub fn resolve<T: ?Sized>(&self, target: &T) -> Result<String, ConfigError>
     where
         T: AsRef<str>,
     {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"\$\{?([_a-zA-Z0-9]+)\}?").unwrap();
+        let target = target.as_ref();
+        let mut resolved = String::new();
+        let mut rest = target;
+
+        if rest == "~" || rest.starts_with("~/") {
+            resolved.push_str(self.require("HOME", target)?);
+            rest = &rest[1..];
         }
 
-        RE.captures_iter(target.as_ref())
-            .filter_map(|rmatch| {
-                if let Ok(val) = env::var(&rmatch[1]) {
-                    return Some((rmatch, val));
+        while let Some(pos) = rest.find(|c| c == '$' || c == '{') {
+            resolved.push_str(&rest[..pos]);
+            rest = &rest[pos..];
+
+            if rest.starts_with("{{") {
+                let end = rest.find("}}").map_or(rest.len(), |end| end + 2);
+                resolved.push_str(&rest[..end]);
+                rest = &rest[end..];
+            } else if rest.starts_with('{') {
+                resolved.push('{');
+                rest = &rest[1..];
+            } else if rest.starts_with("$$") {
+                resolved.push('$');
+                rest = &rest[2..];
+            } else if rest.starts_with("${") {
+                let end = rest.find('}').ok_or_else(|| ConfigError::MalformedVariable {
+                    template: String::from(target),
+                })?;
+
+                let inner = &rest[2..end];
+                let (name, default) = match inner.find(":-") {
+                    Some(split) => (&inner[..split], Some(&inner[split + 2..])),
+                    None => (inner, None),
+                };
+
+                if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    return Err(ConfigError::MalformedVariable {
+                        template: String::from(target),
+                    });
                 }
-                None
-            })
-            .fold(String::from(target.as_ref()), |e, (rmatch, val)| {
-                e.replace(&rmatch[0], &val)
+
+                match (self.variable(name), default) {
+                    (Some(val), Some(default)) if val.is_empty() => resolved.push_str(default),
+                    (Some(val), _) => resolved.push_str(val),
+                    (None, Some(default)) => resolved.push_str(default),
+                    (None, None) => resolved.push_str(self.require(name, target)?),
+                }
+
+                rest = &rest[end + 1..];
+            } else {
+                let len = rest[1..]
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len() - 1);
+
+                if len == 0 {
+                    resolved.push('$');
+                } else {
+                    resolved.push_str(self.require(&rest[1..1 + len], target)?);
+                }
+
+                rest = &rest[1 + len..];
+            }
+        }
+
+        resolved.push_str(rest);
+
+        Ok(resolved)
+    }
+
+    fn require(&self, name: &str, template: &str) -> Result<&String, ConfigError> {
+        self.variable(name)
+            .ok_or_else(|| ConfigError::UndefinedVariable {
+                name: String::from(name),
+                template: String::from(template),
             })
     }
 
+    /// Returns the variable a field is read from along with its value, when
+    /// it is set and not empty.
+    fn lookup(&self, field: &str) -> Option<(&'static str, &String)> {
+        VARIABLES
+            .iter()
+            .filter(|&&(name, _)| name == field)
+            .filter_map(|&(_, var)| self.variable(var).map(|val| (var, val)))
+            .find(|&(_, val)| !val.is_empty())
+    }
+
+    pub fn get_archives(&self) -> Option<Vec<String>> {
+        self.lookup("archives").map(|(_, val)| {
+            env::split_paths(val)
+                .map(|path| path.to_string_lossy().into_owned())
+                .filter(|path| !path.is_empty())
+                .collect()
+        })
+    }
+
     pub fn get_config(&self) -> Option<&String> {
-        self.variable("RXR_CONFIG")
+        self.lookup("config").map(|(_, val)| val)
+    }
+
+    pub fn get_config_dir(&self) -> Option<&String> {
+        self.lookup("config_dir").map(|(_, val)| val)
     }
 
     pub fn get_data_dir(&self) -> Option<&String> {
-        self.0
-            .get("RXR_DATA_DIR")
-            .or_else(|| self.0.get("XDG_DATA_HOME"))
+        self.lookup("data_dir").map(|(_, val)| val)
     }
 
     pub fn get_temp_dir(&self) -> Option<&String> {
-        self.0.get("RXR_TEMP_DIR")
+        self.lookup("temp_dir").map(|(_, val)| val)
     }
 
     pub fn get_target_dir(&self) -> Option<&String> {
-        self.0.get("RXR_TARGET_DIR")
+        self.lookup("target_dir").map(|(_, val)| val)
     }
 
     pub fn get_extractor(&self) -> Option<&String> {
-        self.0.get("RXR_EXTRACTOR_DIR")
+        self.lookup("extractor").map(|(_, val)| val)
     }
 
     pub fn get_profile(&self) -> Option<&String> {
-        self.0.get("RXR_TEMP_DIR")
+        self.lookup("profile").map(|(_, val)| val)
     }
-}
 
-impl From<Environment> for Source {
-    fn from(environment: Environment) -> Source {
+    pub fn get_dry_run(&self) -> Result<Option<bool>, ConfigError> {
+        match self.lookup("dry_run") {
+            Some((var, val)) => match val.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "on" => Ok(Some(true)),
+                "0" | "false" | "no" | "off" => Ok(Some(false)),
+                _ => Err(ConfigError::InvalidVariable {
+                    name: String::from(var),
+                    value: val.clone(),
+                    expected: "a boolean such as 1, true, yes, 0, false or no",
+                }),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the rxr directory of an XDG base directory, along with the
+    /// variable it came from. Relative paths are ignored, as the
+    /// specification asks.
+    fn xdg_dir(&self, var: &'static str, default: &str) -> Option<(&'static str, String)> {
+        let absolute = |dir: &&String| Path::new(dir.as_str()).is_absolute();
+
+        let (var, base) = match self.variable(var).filter(absolute) {
+            Some(dir) => (var, PathBuf::from(dir)),
+            None => ("HOME", Path::new(self.variable("HOME").filter(absolute)?).join(default)),
+        };
+
+        Some((var, base.join("rxr").to_string_lossy().into_owned()))
+    }
+
+    /// The rxr directories of `$XDG_CONFIG_HOME` and `$XDG_CONFIG_DIRS`, in
+    /// order of preference, along with where each came from.
+    fn xdg_config_dirs(&self, compiled: &Compiled) -> Vec<(Origin, PathBuf)> {
+        let mut dirs: Vec<(Origin, PathBuf)> = self.xdg_dir("XDG_CONFIG_HOME", ".config")
+            .into_iter()
+            .map(|(var, dir)| (Origin::Environment(var), PathBuf::from(dir)))
+            .collect();
+
+        let (origin, system_dirs) = match self.variable("XDG_CONFIG_DIRS").filter(|dirs| !dirs.is_empty()) {
+            Some(system_dirs) => (Origin::Environment("XDG_CONFIG_DIRS"), system_dirs.as_str()),
+            None => (
+                Origin::Derived("the default XDG_CONFIG_DIRS"),
+                compiled.xdg_config_dirs.as_ref().map_or("", String::as_str),
+            ),
+        };
+
+        for dir in env::split_paths(system_dirs).filter(|dir| dir.is_absolute()) {
+            dirs.push((origin.clone(), dir.join("rxr")));
+        }
+
+        dirs
+    }
+
+    /// Finds the first config file in the XDG config directories.
+    pub fn xdg_config(&self, compiled: &Compiled) -> Option<(Origin, String)> {
+        self.xdg_config_dirs(compiled)
+            .into_iter()
+            .filter_map(|(origin, dir)| Configuration::find_config(&dir).map(|path| (origin, path)))
+            .next()
+    }
+
+    /// The user's config file under `$XDG_CONFIG_HOME`, whether or not it
+    /// exists, along with the variable it came from.
+    pub fn xdg_config_home(&self) -> Option<(&'static str, String)> {
+        self.xdg_dir("XDG_CONFIG_HOME", ".config")
+            .map(|(var, dir)| (var, Configuration::dir_config(&dir)))
+    }
+
+    /// The options given through `RXR_*` variables, see `VARIABLES`. The
+    /// config file of `RXR_CONFIG_DIR` is used when `RXR_CONFIG` isn't set.
+    pub fn source(&self) -> Result<Source, ConfigError> {
+        let config = self.get_config()
+            .cloned()
+            .or_else(|| self.get_config_dir().map(Configuration::dir_config));
+
+        Ok(Source {
+            archives: self.get_archives(),
+            config: config,
+            data_dir: self.get_data_dir().cloned(),
+            temp_dir: self.get_temp_dir().cloned(),
+            target_dir: self.get_target_dir().cloned(),
+            extractor: self.get_extractor().cloned(),
+            profile: self.get_profile().cloned(),
+            dry_run: self.get_dry_run()?,
+            environment: Some(self.clone()),
+            ..Default::default()
+        }.with_origins(|field| {
+            self.lookup(field)
+                .or_else(|| self.lookup("config_dir").filter(|_| field == "config"))
+                .map(|(var, _)| Origin::Environment(var))
+        }))
+    }
+
+    /// The config file, data and temp directories under the XDG base
+    /// directories, which every other layer takes precedence over. The config
+    /// is the first one found, or else where `config init` writes it.
+    pub fn xdg_source(&self, compiled: &Compiled) -> Source {
+        let config = self.xdg_config(compiled).or_else(|| {
+            self.xdg_config_home()
+                .map(|(var, path)| (Origin::Environment(var), path))
+        });
+        let dirs: Vec<(&str, &'static str, String)> = XDG_DIRS
+            .iter()
+            .filter_map(|&(var, default, field)| {
+                self.xdg_dir(var, default).map(|(var, dir)| (field, var, dir))
+            })
+            .collect();
+        let dir = |field: &str| {
+            dirs.iter()
+                .find(|&&(name, _, _)| name == field)
+                .map(|&(_, var, ref dir)| (var, dir.clone()))
+        };
+
         Source {
-            config: environment.get_config().map(String::clone),
-            data_dir: environment.get_data_dir().map(String::clone),
-            temp_dir: environment.get_temp_dir().map(String::clone),
-            target_dir: environment.get_target_dir().map(String::clone),
-            extractor: environment.get_extractor().map(String::clone),
-            profile: environment.get_profile().map(String::clone),
+            config: config.as_ref().map(|&(_, ref path)| path.clone()),
+            data_dir: dir("data_dir").map(|(_, dir)| dir),
+            temp_dir: dir("temp_dir").map(|(_, dir)| dir),
             ..Default::default()
-        }
+        }.with_origins(|field| match field {
+            "config" => config.as_ref().map(|&(ref origin, _)| origin.clone()),
+            field => dir(field).map(|(var, _)| Origin::Environment(var)),
+        })
     }
 }
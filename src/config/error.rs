@@ -3,16 +3,20 @@ use std::error::Error;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ConfigError {
-    NoConfig,
     NoExtractors,
     NoProfiles,
     NoTemp,
+    NoData,
+    InvalidTemplate { owner: String, reason: String },
+    DuplicateEntry { kind: String, name: String, path: String },
+    UndefinedVariable { name: String, template: String },
+    MalformedVariable { template: String },
+    InvalidVariable { name: String, value: String, expected: &'static str },
 }
 
 impl fmt::Display for ConfigError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ConfigError::NoConfig => write!(f, "no configuration file was provided"),
             ConfigError::NoExtractors => {
                 write!(f, "no extractors where provided in the config file")
             }
@@ -21,6 +25,43 @@ impl fmt::Display for ConfigError {
                 f,
                 "no temp or target directory was provided in the config file"
             ),
+            ConfigError::NoData => write!(
+                f,
+                "no data directory was provided, use --data-directory, RXR_DATA_DIR or XDG_DATA_HOME"
+            ),
+            ConfigError::InvalidTemplate {
+                ref owner,
+                ref reason,
+            } => write!(f, "invalid template in {}: {}", owner, reason),
+            ConfigError::DuplicateEntry {
+                ref kind,
+                ref name,
+                ref path,
+            } => write!(
+                f,
+                "{} '{}' in {} is already defined, set \"override\": true to replace it",
+                kind, name, path
+            ),
+            ConfigError::UndefinedVariable {
+                ref name,
+                ref template,
+            } => write!(
+                f,
+                "environment variable '{}' used in '{}' is not defined",
+                name, template
+            ),
+            ConfigError::MalformedVariable { ref template } => {
+                write!(f, "malformed environment variable in '{}'", template)
+            }
+            ConfigError::InvalidVariable {
+                ref name,
+                ref value,
+                expected,
+            } => write!(
+                f,
+                "environment variable '{}' is '{}', expected {}",
+                name, value, expected
+            ),
         }
     }
 }
@@ -28,10 +69,15 @@ impl fmt::Display for ConfigError {
 impl Error for ConfigError {
     fn description(&self) -> &str {
         match *self {
-            ConfigError::NoConfig => "no configuration file was provided",
             ConfigError::NoExtractors => "no extractors where provided in the config file",
             ConfigError::NoProfiles => "no profiles where provided in the config file",
             ConfigError::NoTemp => "no temp or target directory was provided in the config file",
+            ConfigError::NoData => "no data directory was provided",
+            ConfigError::InvalidTemplate { .. } => "invalid template in the config file",
+            ConfigError::DuplicateEntry { .. } => "duplicate entry in the config directory",
+            ConfigError::UndefinedVariable { .. } => "undefined environment variable",
+            ConfigError::MalformedVariable { .. } => "malformed environment variable",
+            ConfigError::InvalidVariable { .. } => "invalid environment variable",
         }
     }
 }
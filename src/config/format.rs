@@ -0,0 +1,102 @@
+extern crate serde_json;
+extern crate serde_yaml;
+
+use serde_json::Value;
+use toml;
+
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+/// The formats a configuration file can be written in. Every format is read
+/// into JSON values first, so they all share the same schema.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// Picks the format from the extension of a path, if it is a known one.
+    pub fn from_extension<T>(path: &T) -> Option<Format>
+    where
+        T: AsRef<Path>,
+    {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.to_lowercase().parse().ok())
+    }
+
+    /// Picks the format from the extension of a path, defaulting to JSON.
+    pub fn from_path<T>(path: &T) -> Format
+    where
+        T: AsRef<Path>,
+    {
+        Format::from_extension(path).unwrap_or(Format::Json)
+    }
+
+    pub fn parse(&self, text: &str) -> Result<Value, Box<Error>> {
+        match *self {
+            Format::Json => Ok(serde_json::from_str(text)?),
+            Format::Toml => Ok(toml::from_str(text)?),
+            Format::Yaml => Ok(serde_yaml::from_str(text)?),
+        }
+    }
+
+    pub fn render(&self, value: &Value) -> Result<String, Box<Error>> {
+        match *self {
+            Format::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+            Format::Toml => {
+                if let Some(path) = find_null(value) {
+                    return Err(From::from(format!(
+                        "{} is null, which TOML cannot express, use JSON or YAML instead",
+                        path
+                    )));
+                }
+
+                // Going through TOML's own values puts plain values before tables.
+                Ok(toml::to_string(&toml::Value::try_from(value)?)?)
+            }
+            Format::Yaml => Ok(serde_yaml::to_string(value)? + "\n"),
+        }
+    }
+}
+
+/// Returns the path of the first `null` in a value, such as `profiles.msdos`.
+fn find_null(value: &Value) -> Option<String> {
+    let child = |key: String, value: &Value| match *value {
+        Value::Null => Some(key),
+        ref value => find_null(value).map(|path| format!("{}.{}", key, path)),
+    };
+
+    match *value {
+        Value::Object(ref object) => object
+            .iter()
+            .filter_map(|(key, value)| child(key.clone(), value))
+            .next(),
+        Value::Array(ref array) => array
+            .iter()
+            .enumerate()
+            .filter_map(|(index, value)| child(index.to_string(), value))
+            .next(),
+        _ => None,
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            "yaml" | "yml" => Ok(Format::Yaml),
+            _ => Err(format!(
+                "unknown format '{}', expected json, toml or yaml",
+                s
+            )),
+        }
+    }
+}
@@ -1,10 +1,23 @@
+mod check;
 mod commandline;
 mod compiled;
 mod configuration;
 mod environment;
 mod error;
+mod format;
+mod origin;
 mod persisted;
+mod sidecar;
 mod source;
+mod subcommand;
 
-pub use self::configuration::Configuration;
+pub use self::check::{check_texts, Problem};
+pub use self::compiled::Compiled;
+pub use self::configuration::{Configuration, ConfigurationBuilder};
 pub use self::environment::Environment;
+pub use self::error::ConfigError;
+pub use self::format::Format;
+pub use self::origin::Origin;
+pub use self::persisted::Persisted;
+pub use self::sidecar::Sidecar;
+pub use self::subcommand::ConfigCommand;
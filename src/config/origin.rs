@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    /// A command line flag.
+    CommandLine(&'static str),
+    /// An environment variable.
+    Environment(&'static str),
+    /// A configuration file, with the line the entry starts at when known.
+    File { path: String, line: Option<usize> },
+    /// An environment variable set when rxr was compiled.
+    Compiled(&'static str),
+    /// Computed from other values.
+    Derived(&'static str),
+}
+
+/// The origins of the values of a configuration layer, keyed by field name or
+/// by `extractors.<name>` and `profiles.<name>`. Entries merged from several
+/// files list every one of them in order.
+pub type Origins = HashMap<String, Vec<Origin>>;
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Origin::CommandLine(flag) => write!(f, "command line {}", flag),
+            Origin::Environment(var) => write!(f, "environment ${}", var),
+            Origin::File {
+                ref path,
+                line: Some(line),
+            } => write!(f, "{}:{}", path, line),
+            Origin::File { ref path, .. } => write!(f, "{}", path),
+            Origin::Compiled(var) => write!(f, "compile-time ${}", var),
+            Origin::Derived(from) => write!(f, "derived from {}", from),
+        }
+    }
+}
+
+/// Returns whether a line sets one of the given keys.
+fn is_key(line: &str, keys: &[String]) -> bool {
+    keys.iter().any(|key| {
+        line.starts_with(key.as_str())
+            && line[key.len()..].trim_start().starts_with(|c| c == ':' || c == '=')
+    })
+}
+
+/// The spellings of a key in JSON, TOML and YAML.
+fn spellings(key: &str) -> Vec<String> {
+    vec![format!("\"{}\"", key), format!("'{}'", key), String::from(key)]
+}
+
+/// Returns the line at which an entry of a section starts in a JSON, TOML or
+/// YAML file: its own `[section.name]` table, or its key directly inside the
+/// section's key or `[section]` table. Comments are skipped.
+pub fn entry_line(text: &str, section: &str, name: &str) -> Option<usize> {
+    let table = format!("[{}.{}", section, name);
+    let header = format!("[{}]", section);
+    let section_keys = spellings(section);
+    let entry_keys = spellings(name);
+
+    // The indentation of the section's key, none for a TOML table, and the
+    // indentation of its entries once seen.
+    let mut scope: Option<(Option<usize>, Option<usize>)> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let indent = line.len() - trimmed.len();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("//") {
+            continue;
+        }
+
+        if trimmed.starts_with(&table) && trimmed[table.len()..].starts_with(|c| c == '.' || c == ']') {
+            return Some(index + 1);
+        }
+
+        scope = match scope {
+            Some((Some(section_indent), _)) if indent <= section_indent => None,
+            Some((None, _)) if trimmed.starts_with('[') => None,
+            scope => scope,
+        };
+
+        if let Some((section_indent, ref mut entry_indent)) = scope {
+            let entry_indent = entry_indent.get_or_insert(indent);
+
+            if is_key(trimmed, &entry_keys) && (section_indent.is_none() || *entry_indent == indent) {
+                return Some(index + 1);
+            }
+        } else if trimmed.starts_with(&header) {
+            scope = Some((None, None));
+        } else if is_key(trimmed, &section_keys) {
+            scope = Some((Some(indent), None));
+        }
+    }
+
+    None
+}
@@ -2,29 +2,368 @@ use super::source::Source;
 
 extern crate serde_json;
 
+use config::error::ConfigError;
+use config::format::Format;
+use config::origin::{entry_line, Origin, Origins};
 use extractor::Extractor;
 use profile::Profile;
 
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SubschemaValidation};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
 use std::collections::HashMap;
 use std::error::Error;
 use std::io::prelude::*;
 use std::path::Path;
+use std::path::PathBuf;
+
+/// The configuration built into rxr, read when no config file is found.
+pub const DEFAULT_CONFIG: &str = include_str!("../../config/default.json");
+
+/// The path the built-in configuration is read and reported as.
+pub const DEFAULT_CONFIG_PATH: &str = "<built-in>";
 
-#[derive(Deserialize, Debug, Default)]
+/// The extractors and profiles of a configuration file.
+#[derive(Deserialize, JsonSchema, Debug, Default)]
 pub struct Persisted {
     pub extractors: Option<HashMap<String, Extractor>>,
     pub profiles: Option<HashMap<String, Profile>>,
+
+    #[serde(skip)]
+    pub origins: Origins,
 }
 
 impl Persisted {
-    pub fn read<T>(path: &T) -> Result<Persisted, Box<Error>>
+    /// Reads config files in order, merging every one into the previous ones
+    /// per profile, extractor and field, and then adds the drop-ins of the
+    /// config directory. A `null` removes whatever it replaces.
+    pub fn read_layers<T, U>(paths: &[T], dir: Option<&U>) -> Result<Persisted, Box<Error>>
     where
         T: AsRef<Path>,
+        U: AsRef<Path>,
+    {
+        let (merged, origins) = merge_layers(paths, dir)?;
+        Persisted::from_value(merged, origins)
+    }
+
+    /// Reads in-memory config files as `read_layers` does, each given as the
+    /// name it is reported under, whose extension gives its format, along
+    /// with its contents.
+    pub fn read_texts<U>(texts: &[(String, String)], dir: Option<&U>) -> Result<Persisted, Box<Error>>
+    where
+        U: AsRef<Path>,
     {
-        let mut json = String::new();
-        ::fs::File::open(&path)?.read_to_string(&mut json)?;
-        Ok(serde_json::from_str(&json)?)
+        let (merged, origins) = merge_texts(texts, dir)?;
+        Persisted::from_value(merged, origins)
+    }
+
+    fn from_value(mut merged: Value, origins: Origins) -> Result<Persisted, Box<Error>> {
+        Ok(Persisted {
+            extractors: entries(&mut merged, "extractors", "extractor")?,
+            profiles: entries(&mut merged, "profiles", "profile")?,
+            origins: origins,
+        })
     }
+
+    /// Describes the configuration file format as a JSON Schema. As any
+    /// config file may be a layer over others, nothing is required and every
+    /// property may be `null`.
+    pub fn schema() -> RootSchema {
+        let mut schema = schema_for!(Persisted);
+        schema.schema.metadata().title = Some(String::from("rxr configuration"));
+
+        let mut refs = Vec::new();
+        let mut overlaid = Vec::new();
+        overlay(&mut schema.schema, &mut refs);
+
+        while let Some(name) = refs.pop() {
+            if overlaid.contains(&name) {
+                continue;
+            }
+
+            if let Some(&mut Schema::Object(ref mut definition)) = schema.definitions.get_mut(&name) {
+                overlay(definition, &mut refs);
+            }
+            overlaid.push(name);
+        }
+
+        schema
+    }
+}
+
+/// Makes the properties of a schema, and of the schemas it nests, optional
+/// and removable with `null`, collecting the definitions it refers to. Array
+/// items are left alone, as arrays are replaced rather than merged.
+fn overlay(schema: &mut SchemaObject, refs: &mut Vec<String>) {
+    if let Some(ref reference) = schema.reference {
+        refs.push(reference.trim_start_matches("#/definitions/").to_string());
+    }
+
+    if let Some(ref mut object) = schema.object {
+        object.required.clear();
+
+        let additional = object.additional_properties.iter_mut().map(|schema| &mut **schema);
+        for property in object.properties.values_mut().chain(additional) {
+            if let Schema::Object(ref mut property) = *property {
+                overlay(property, refs);
+                nullable(property);
+            }
+        }
+    }
+
+    if let Some(ref mut subschemas) = schema.subschemas {
+        let all = subschemas.all_of.iter_mut().flat_map(|schemas| schemas.iter_mut());
+        let any = subschemas.any_of.iter_mut().flat_map(|schemas| schemas.iter_mut());
+        let one = subschemas.one_of.iter_mut().flat_map(|schemas| schemas.iter_mut());
+
+        for subschema in all.chain(any).chain(one) {
+            if let Schema::Object(ref mut subschema) = *subschema {
+                overlay(subschema, refs);
+            }
+        }
+    }
+}
+
+fn is_null(schema: &SchemaObject) -> bool {
+    schema.instance_type.as_ref().map_or(false, |instance_type| instance_type.contains(&InstanceType::Null))
+}
+
+/// Lets a schema also be `null`, keeping its description.
+fn nullable(schema: &mut SchemaObject) {
+    let any_null = schema
+        .subschemas
+        .as_ref()
+        .and_then(|subschemas| subschemas.any_of.as_ref())
+        .map_or(false, |schemas| {
+            schemas.iter().any(|schema| match *schema {
+                Schema::Object(ref schema) => is_null(schema),
+                Schema::Bool(_) => false,
+            })
+        });
+
+    if is_null(schema) || any_null {
+        return;
+    }
+
+    let metadata = schema.metadata.take();
+    let null = SchemaObject {
+        instance_type: Some(InstanceType::Null.into()),
+        ..Default::default()
+    };
+
+    *schema = SchemaObject {
+        metadata: metadata,
+        subschemas: Some(Box::new(SubschemaValidation {
+            any_of: Some(vec![Schema::Object(schema.clone()), null.into()]),
+            ..Default::default()
+        })),
+        ..Default::default()
+    };
+}
+
+/// Merges config files and drop-ins as `Persisted::read_layers` does, without
+/// deserialising the result.
+pub fn merge_layers<T, U>(paths: &[T], dir: Option<&U>) -> Result<(Value, Origins), Box<Error>>
+where
+    T: AsRef<Path>,
+    U: AsRef<Path>,
+{
+    let mut texts = Vec::new();
+
+    for path in paths {
+        texts.push((path.as_ref().to_string_lossy().into_owned(), read_text(path)?));
+    }
+
+    merge_texts(&texts, dir)
+}
+
+/// Merges in-memory config files and drop-ins as `Persisted::read_texts`
+/// does, without deserialising the result.
+pub fn merge_texts<U>(texts: &[(String, String)], dir: Option<&U>) -> Result<(Value, Origins), Box<Error>>
+where
+    U: AsRef<Path>,
+{
+    let mut merged = Value::Object(Map::new());
+    let mut origins = Origins::new();
+
+    for &(ref path, ref text) in texts {
+        let layer = parse(path, text)?;
+
+        for section in &["extractors", "profiles"] {
+            let entries = layer.get(section).and_then(Value::as_object);
+
+            for (name, entry) in entries.into_iter().flat_map(|entries| entries) {
+                let key = format!("{}.{}", section, name);
+
+                if entry.is_null() {
+                    origins.remove(&key);
+                } else {
+                    origins.entry(key).or_insert_with(Vec::new).push(Origin::File {
+                        path: path.clone(),
+                        line: entry_line(text, section, name),
+                    });
+                }
+            }
+        }
+
+        merge_value(&mut merged, layer);
+    }
+
+    if let Some(dir) = dir {
+        let dir = dir.as_ref();
+        for &(section, kind) in &[("extractors", "extractor"), ("profiles", "profile")] {
+            let dir = dir.join(format!("{}.d", section));
+            merge_drop_ins(&mut merged, &mut origins, &dir, section, kind)?;
+        }
+    }
+
+    Ok((merged, origins))
+}
+
+/// Reads a config file, or the built-in configuration for
+/// `DEFAULT_CONFIG_PATH`.
+pub fn read_text<T>(path: &T) -> Result<String, Box<Error>>
+where
+    T: AsRef<Path>,
+{
+    if path.as_ref() == Path::new(DEFAULT_CONFIG_PATH) {
+        return Ok(String::from(DEFAULT_CONFIG));
+    }
+
+    let mut text = String::new();
+    ::fs::File::open(&path)?.read_to_string(&mut text)?;
+    Ok(text)
+}
+
+fn parse<T>(path: &T, text: &str) -> Result<Value, Box<Error>>
+where
+    T: AsRef<Path>,
+{
+    Ok(Format::from_path(path)
+        .parse(text)
+        .map_err(|err| format!("{}: {}", path.as_ref().display(), err))?)
+}
+
+/// Reads a config file in the format given by its extension.
+pub fn read_value<T>(path: &T) -> Result<Value, Box<Error>>
+where
+    T: AsRef<Path>,
+{
+    parse(path, &read_text(path)?)
+}
+
+/// Merges `layer` into `value`: objects are merged per key, a `null` removes
+/// the key and anything else replaces the previous value.
+fn merge_value(value: &mut Value, layer: Value) {
+    match layer {
+        Value::Object(layer) if value.is_object() => {
+            let object = value.as_object_mut().unwrap();
+
+            for (key, val) in layer {
+                if val.is_null() {
+                    object.remove(&key);
+                } else if object.contains_key(&key) {
+                    merge_value(object.get_mut(&key).unwrap(), val);
+                } else {
+                    object.insert(key, val);
+                }
+            }
+        }
+        layer => *value = layer,
+    }
+}
+
+/// Deserialises the entries of a section, naming the entry that fails.
+fn entries<V>(
+    value: &mut Value,
+    section: &str,
+    kind: &str,
+) -> Result<Option<HashMap<String, V>>, Box<Error>>
+where
+    V: DeserializeOwned,
+{
+    let object = match value.as_object_mut().and_then(|object| object.remove(section)) {
+        Some(Value::Object(object)) => object,
+        Some(Value::Null) | None => return Ok(None),
+        Some(_) => return Err(From::from(format!("'{}' must be an object", section))),
+    };
+
+    let mut entries = HashMap::new();
+
+    for (name, entry) in object {
+        let entry = serde_json::from_value(entry)
+            .map_err(|err| format!("{} '{}': {}", kind, name, err))?;
+        entries.insert(name, entry);
+    }
+
+    Ok(Some(entries))
+}
+
+/// Adds the drop-ins of a `profiles.d` or `extractors.d` directory to a
+/// section, one per JSON, TOML or YAML file named after the entry, in lexical
+/// order. A drop-in may only be merged into an entry of the same name when it
+/// sets `"override": true`.
+fn merge_drop_ins(
+    merged: &mut Value,
+    origins: &mut Origins,
+    dir: &Path,
+    section: &str,
+    kind: &str,
+) -> Result<(), Box<Error>> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    let mut paths: Vec<PathBuf> = ::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| Format::from_extension(path).is_some())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+
+        let mut drop_in = read_value(&path)?;
+        let is_override = drop_in
+            .as_object_mut()
+            .and_then(|object| object.remove("override"))
+            .and_then(|is_override| is_override.as_bool())
+            .unwrap_or(false);
+
+        let exists = merged
+            .get(section)
+            .and_then(|entries| entries.get(&name))
+            .is_some();
+
+        if exists && !is_override {
+            return Err(Box::new(ConfigError::DuplicateEntry {
+                kind: String::from(kind),
+                name: name,
+                path: path.to_string_lossy().into_owned(),
+            }));
+        }
+
+        let origin = Origin::File {
+            path: path.to_string_lossy().into_owned(),
+            line: None,
+        };
+        origins
+            .entry(format!("{}.{}", section, name))
+            .or_insert_with(Vec::new)
+            .push(origin);
+
+        let mut entries = Map::new();
+        entries.insert(name, drop_in);
+
+        let mut layer = Map::new();
+        layer.insert(String::from(section), Value::Object(entries));
+
+        merge_value(merged, Value::Object(layer));
+    }
+
+    Ok(())
 }
 
 impl From<Persisted> for Source {
@@ -32,6 +371,7 @@ impl From<Persisted> for Source {
         Source {
             extractors: persisted.extractors,
             profiles: persisted.profiles,
+            origins: persisted.origins,
             ..Default::default()
         }
     }
@@ -0,0 +1,159 @@
+extern crate serde_json;
+
+use config::format::Format;
+use config::origin::Origin;
+use config::persisted;
+use config::source::Source;
+use command::Argument;
+use dosbox_config::{AutoexecMode, DosboxConfig, MergeOptions};
+use profile::Profile;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Per-archive overrides, read from `<archive>.rxr.json` next to the archive
+/// and from `.rxr.json` in the extracted root, in JSON, TOML or YAML. The one
+/// next to the archive takes precedence.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sidecar {
+    /// Profile to run, which only `--profile` takes precedence over.
+    pub profile: Option<String>,
+
+    /// Executable to run, relative to the extracted root, instead of asking
+    /// when the profile matches several.
+    pub executable: Option<String>,
+
+    /// Environment variables added to the profile's command.
+    pub evars: HashMap<String, String>,
+
+    /// Arguments appended to the profile's command.
+    pub args: Vec<String>,
+
+    /// DOSBox settings and autoexec commands layered over the profile's own,
+    /// for profiles generating a DOSBox config.
+    pub dosbox: Option<DosboxConfig>,
+}
+
+impl Sidecar {
+    /// The sidecar files of an archive that exist, in order of increasing
+    /// precedence.
+    pub fn find<T, U>(archive: &T, target_dir: &U) -> Vec<PathBuf>
+    where
+        T: AsRef<Path>,
+        U: AsRef<Path>,
+    {
+        let archive = archive.as_ref().to_string_lossy();
+        let root = target_dir.as_ref().join(".rxr");
+
+        ["json", "toml", "yaml", "yml"]
+            .iter()
+            .map(|extension| root.with_extension(extension))
+            .chain(
+                ["json", "toml", "yaml", "yml"]
+                    .iter()
+                    .map(|extension| PathBuf::from(format!("{}.rxr.{}", archive, extension))),
+            )
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Reads sidecar files, in order of increasing precedence, into a layer
+    /// of the configuration setting the profile and the sidecar.
+    pub fn source(paths: &[PathBuf]) -> Result<Source, Box<Error>> {
+        let mut sidecar = Sidecar::default();
+        let mut origins = Vec::new();
+        let mut profile_origin = None;
+
+        for path in paths {
+            let origin = Origin::File {
+                path: path.to_string_lossy().into_owned(),
+                line: None,
+            };
+            let layer = Sidecar::read(path)?;
+
+            if layer.profile.is_some() {
+                profile_origin = Some(origin.clone());
+            }
+
+            sidecar = layer.merge(sidecar);
+            origins.push(origin);
+        }
+
+        let mut source = Source {
+            profile: sidecar.profile.clone(),
+            sidecar: Some(sidecar),
+            ..Default::default()
+        }.with_origins(|field| profile_origin.clone().filter(|_| field == "profile"));
+        source.origins.insert(String::from("sidecar"), origins);
+
+        Ok(source)
+    }
+
+    pub fn read<T>(path: &T) -> Result<Sidecar, Box<Error>>
+    where
+        T: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let value = Format::from_path(&path).parse(&persisted::read_text(&path)?)?;
+
+        Ok(serde_json::from_value(value).map_err(|err| format!("{}: {}", path.display(), err))?)
+    }
+
+    /// Combines two sidecars, preferring the values of `self` and appending
+    /// its arguments to those of `other`.
+    pub fn merge(self, other: Sidecar) -> Sidecar {
+        let mut evars = other.evars;
+        evars.extend(self.evars);
+
+        let mut args = other.args;
+        args.extend(self.args);
+
+        let dosbox = match (other.dosbox, self.dosbox) {
+            (Some(other), Some(dosbox)) => Some(other.merge(&dosbox)),
+            (other, dosbox) => dosbox.or(other),
+        };
+
+        Sidecar {
+            profile: self.profile.or(other.profile),
+            executable: self.executable.or(other.executable),
+            evars: evars,
+            args: args,
+            dosbox: dosbox,
+        }
+    }
+
+    /// Returns the profile with the sidecar's environment variables,
+    /// arguments and DOSBox settings added. DOSBox settings are refused for
+    /// profiles that don't generate a DOSBox config.
+    pub fn apply(&self, profile: &Profile) -> Result<Profile, Box<Error>> {
+        if self.dosbox.is_some() && profile.dosbox.is_none() {
+            return Err(From::from(
+                "the sidecar files set DOSBox settings, but the profile has no dosbox options",
+            ));
+        }
+
+        let mut profile = profile.clone();
+
+        profile.command.evars.extend(self.evars.clone());
+        profile
+            .command
+            .args
+            .extend(self.args.iter().cloned().map(Argument::Value));
+
+        if let (Some(dosbox), Some(config)) = (profile.dosbox.as_mut(), self.dosbox.as_ref()) {
+            let replace_autoexec = MergeOptions {
+                autoexec: AutoexecMode::Replace,
+                ..Default::default()
+            };
+
+            dosbox.config = Some(match dosbox.config.take() {
+                Some(inline) => inline.merge_with(config, &replace_autoexec),
+                None => config.clone(),
+            });
+        }
+
+        Ok(profile)
+    }
+}
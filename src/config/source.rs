@@ -1,9 +1,12 @@
 use config::configuration::Configuration;
 use config::environment::Environment;
 use config::error::ConfigError;
+use config::origin::{Origin, Origins};
+use config::sidecar::Sidecar;
 
 use extractor::Extractor;
 use profile::Profile;
+use template::Template;
 
 use std::collections::HashMap;
 use std::error::Error;
@@ -22,12 +25,47 @@ pub struct Source {
     pub target_dir: Option<String>,
     pub extractor: Option<String>,
     pub profile: Option<String>,
+    pub dry_run: Option<bool>,
+    pub environment: Option<Environment>,
     pub extractors: Option<HashMap<String, Extractor>>,
     pub profiles: Option<HashMap<String, Profile>>,
+    pub sidecar: Option<Sidecar>,
+
+    #[serde(skip)]
+    pub origins: Origins,
+}
+
+/// The plain fields of a source, whose origins are tracked by name.
+const FIELDS: &[&str] = &[
+    "archives",
+    "config",
+    "data_dir",
+    "temp_dir",
+    "target_dir",
+    "extractor",
+    "profile",
+    "dry_run",
+];
+
+/// Combines the entries of two sources per name, preferring those of `a`.
+fn merge_entries<V>(
+    a: Option<HashMap<String, V>>,
+    b: Option<HashMap<String, V>>,
+) -> Option<HashMap<String, V>> {
+    match (a, b) {
+        (Some(a), Some(mut b)) => {
+            b.extend(a);
+            Some(b)
+        }
+        (a, b) => a.or(b),
+    }
 }
 
 impl Source {
     pub fn merge(self, other: Source) -> Source {
+        let mut origins = other.origins;
+        origins.extend(self.origins);
+
         Source {
             archives: self.archives.or(other.archives),
             config: self.config.or(other.config),
@@ -36,11 +74,77 @@ impl Source {
             target_dir: self.target_dir.or(other.target_dir),
             extractor: self.extractor.or(other.extractor),
             profile: self.profile.or(other.profile),
-            extractors: self.extractors.or(other.extractors),
-            profiles: self.profiles.or(other.profiles),
+            dry_run: self.dry_run.or(other.dry_run),
+            environment: self.environment.or(other.environment),
+            extractors: merge_entries(self.extractors, other.extractors),
+            profiles: merge_entries(self.profiles, other.profiles),
+            sidecar: self.sidecar.or(other.sidecar),
+            origins: origins,
+        }
+    }
+
+    fn is_set(&self, field: &str) -> bool {
+        match field {
+            "archives" => self.archives.is_some(),
+            "config" => self.config.is_some(),
+            "data_dir" => self.data_dir.is_some(),
+            "temp_dir" => self.temp_dir.is_some(),
+            "target_dir" => self.target_dir.is_some(),
+            "extractor" => self.extractor.is_some(),
+            "profile" => self.profile.is_some(),
+            "dry_run" => self.dry_run.is_some(),
+            _ => false,
         }
     }
 
+    /// Records the origin of every plain field this source sets.
+    pub fn with_origins<F>(mut self, origin: F) -> Source
+    where
+        F: Fn(&str) -> Option<Origin>,
+    {
+        for field in FIELDS {
+            if let Some(origin) = origin(field).filter(|_| self.is_set(field)) {
+                self.origins.insert(String::from(*field), vec![origin]);
+            }
+        }
+
+        self
+    }
+
+    pub fn validate_templates(&self) -> Result<(), ConfigError> {
+        let extractors = self.extractors
+            .iter()
+            .flat_map(|extractors| extractors.iter())
+            .map(|(name, extractor)| (format!("extractor '{}'", name), extractor.command()));
+
+        let profiles = self.profiles
+            .iter()
+            .flat_map(|profiles| profiles.iter())
+            .map(|(name, profile)| (format!("profile '{}'", name), &profile.command));
+
+        for (owner, command) in extractors.chain(profiles) {
+            command
+                .validate()
+                .map_err(|err| ConfigError::InvalidTemplate {
+                    owner: owner,
+                    reason: err.to_string(),
+                })?;
+        }
+
+        if let Some(ref sidecar) = self.sidecar {
+            for val in sidecar.args.iter().chain(sidecar.evars.values()) {
+                Template::new(val)
+                    .validate()
+                    .map_err(|err| ConfigError::InvalidTemplate {
+                        owner: String::from("the sidecar files"),
+                        reason: err.to_string(),
+                    })?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn validate(self) -> Result<Configuration, Box<Error>> {
         if self.extractors.is_none() {
             return Err(Box::new(ConfigError::NoExtractors));
@@ -53,13 +157,29 @@ impl Source {
             return Err(Box::new(ConfigError::NoTemp));
         }
 
+        self.validate_templates()?;
+
         //let archives: Vec<PathBuf> = self.archives.unwrap().iter().map(PathBuf::from).collect();
 
-        let mut temp_dir = self.temp_dir.as_ref().map(Environment::expand);
+        let environment = self.environment.clone().unwrap_or_default();
+
+        let expand = |path: &Option<String>| match *path {
+            Some(ref path) => environment.resolve(path).map(Some),
+            None => Ok(None),
+        };
+
+        let mut temp_dir = expand(&self.temp_dir)?;
+
+        let mut target_dir = expand(&self.target_dir)?;
 
-        let mut target_dir = self.target_dir.as_ref().map(Environment::expand);
+        let mut origins = self.origins.clone();
 
         if temp_dir.is_none() {
+            origins.insert(
+                String::from("temp_dir"),
+                vec![Origin::Derived("target_dir")],
+            );
+
             temp_dir = Some(
                 PathBuf::from(target_dir.as_ref().unwrap())
                     .as_path()
@@ -68,7 +188,14 @@ impl Source {
                     .to_string_lossy()
                     .into_owned(),
             );
-        } else {
+        } else if target_dir.is_none()
+            && self.archives.as_ref().map_or(false, |archives| !archives.is_empty())
+        {
+            origins.insert(
+                String::from("target_dir"),
+                vec![Origin::Derived("temp_dir and the archives")],
+            );
+
             let targets: Vec<&str> = self.archives
                 .as_ref()
                 .unwrap()
@@ -101,17 +228,15 @@ impl Source {
             path.push('/');
             path.push_str(&target);
 
-            println!("=====> Final Target Path: {:#?}", path);
-
             target_dir = Some(path);
         }
 
         Ok(Configuration {
             archives: self.archives.unwrap_or_default(),
 
-            config: self.config.as_ref().map(Environment::expand).unwrap(),
+            config: expand(&self.config)?.unwrap_or_default(),
 
-            data_dir: self.data_dir.as_ref().map(Environment::expand).unwrap(),
+            data_dir: expand(&self.data_dir)?.ok_or(ConfigError::NoData)?,
 
             //temp_dir: self.temp_dir.unwrap_or_default(),
             temp_dir: temp_dir.unwrap_or_default(),
@@ -122,9 +247,17 @@ impl Source {
 
             profile: self.profile,
 
+            dry_run: self.dry_run.unwrap_or(false),
+
+            environment: environment.clone(),
+
             extractors: self.extractors.unwrap(),
 
             profiles: self.profiles.unwrap(),
+
+            sidecar: self.sidecar.unwrap_or_default(),
+
+            origins: origins,
         })
     }
 }
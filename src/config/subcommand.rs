@@ -0,0 +1,209 @@
+use config::check;
+use config::commandline::CommandLine;
+use config::compiled::Compiled;
+use config::configuration::Configuration;
+use config::environment::Environment;
+use config::format::Format;
+use config::origin::Origin;
+use config::persisted::{self, Persisted};
+
+use serde::Serialize;
+use serde_json;
+
+use std::collections::HashMap;
+
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// The `config` subcommands, which work on configuration files instead of
+/// extracting and running archives.
+#[derive(Debug)]
+pub enum ConfigCommand {
+    /// Translates a configuration file into another format, printing it when
+    /// no output file is given.
+    Convert {
+        input: String,
+        output: Option<String>,
+        format: Option<Format>,
+    },
+    /// Prints the effective configuration for the given archives, including
+    /// the merged extractors and profiles, along with where every value came
+    /// from.
+    Show,
+    /// Reports every problem found in the configuration files, with the file,
+    /// line and JSON path it was found at.
+    Check,
+    /// Prints the JSON Schema of the configuration file format.
+    Schema,
+    /// Writes the built-in configuration to a file for editing, by default
+    /// the user's config under `$XDG_CONFIG_HOME`.
+    Init {
+        output: Option<String>,
+        format: Option<Format>,
+        force: bool,
+    },
+}
+
+impl ConfigCommand {
+    /// Returns the subcommand given on the command line, if any.
+    pub fn current() -> Option<ConfigCommand> {
+        CommandLine::current().command
+    }
+
+    pub fn run(&self) -> Result<(), Box<Error>> {
+        match *self {
+            ConfigCommand::Convert {
+                ref input,
+                ref output,
+                format,
+            } => convert(input, output.as_ref(), format),
+            ConfigCommand::Show => show(&Configuration::load()?),
+            ConfigCommand::Check => check(),
+            ConfigCommand::Init {
+                ref output,
+                format,
+                force,
+            } => init(output.as_ref(), format, force),
+            ConfigCommand::Schema => {
+                print!("{}", Format::Json.render(&serde_json::to_value(Persisted::schema())?)?);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn convert(input: &str, output: Option<&String>, format: Option<Format>) -> Result<(), Box<Error>> {
+    let format = format
+        .or_else(|| output.and_then(Format::from_extension))
+        .ok_or("no output format was given, use --to or an output file ending in .json, .toml or .yaml")?;
+
+    let converted = format.render(&persisted::read_value(&input)?)?;
+
+    match output {
+        Some(output) => fs::write(output, converted)?,
+        None => print!("{}", converted),
+    }
+
+    Ok(())
+}
+
+fn init(output: Option<&String>, format: Option<Format>, force: bool) -> Result<(), Box<Error>> {
+    let output = match output {
+        Some(output) => output.clone(),
+        None => Environment::current()
+            .xdg_config_home()
+            .map(|(_, path)| path)
+            .ok_or("no output file was given and neither XDG_CONFIG_HOME nor HOME is set")?,
+    };
+
+    if Path::new(&output).exists() && !force {
+        return Err(From::from(format!(
+            "{} already exists, use --force to overwrite it",
+            output
+        )));
+    }
+
+    // The built-in config is written as is in JSON, to keep its order.
+    let config = match format.unwrap_or_else(|| Format::from_path(&output)) {
+        Format::Json => String::from(persisted::DEFAULT_CONFIG),
+        format => format.render(&Format::Json.parse(persisted::DEFAULT_CONFIG)?)?,
+    };
+
+    if let Some(dir) = Path::new(&output).parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(&output, config)?;
+    println!("Wrote the default configuration to {}", output);
+
+    Ok(())
+}
+
+fn check() -> Result<(), Box<Error>> {
+    let args = CommandLine::current();
+    let environment = Environment::current();
+    let compiled = Compiled::new();
+    let layers = Configuration::layers(&args, &environment, &compiled);
+    let problems = check::check(&layers, Configuration::config_dir(&args, &environment, &compiled).as_ref())?;
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    let errors = problems.iter().filter(|problem| !problem.warning).count();
+
+    if problems.is_empty() {
+        println!("No problems found in {}.", layers.join(", "));
+    }
+
+    if errors == 0 {
+        Ok(())
+    } else {
+        Err(From::from(format!("found {} problem(s)", errors)))
+    }
+}
+
+/// Describes where a value came from.
+fn origins(config: &Configuration, key: &str) -> String {
+    let origins: Vec<String> = config
+        .origins
+        .get(key)
+        .iter()
+        .flat_map(|origins| origins.iter())
+        .map(Origin::to_string)
+        .collect();
+
+    if origins.is_empty() {
+        String::from("default")
+    } else {
+        origins.join(", ")
+    }
+}
+
+fn show(config: &Configuration) -> Result<(), Box<Error>> {
+    let fields = [
+        ("archives", config.archives.join(" ")),
+        ("config", config.config.clone()),
+        ("data_dir", config.data_dir.clone()),
+        ("temp_dir", config.temp_dir.clone()),
+        ("target_dir", config.target_dir.clone()),
+        ("extractor", config.extractor.clone().unwrap_or_default()),
+        ("profile", config.profile.clone().unwrap_or_default()),
+        ("dry_run", config.dry_run.to_string()),
+    ];
+
+    for &(field, ref val) in &fields {
+        println!("{:<10} = {:<40} ({})", field, val, origins(config, field));
+    }
+
+    if config.origins.contains_key("sidecar") {
+        println!("{:<10}   {:<40} ({})", "sidecar", "", origins(config, "sidecar"));
+    }
+
+    show_entries(config, "extractors", &config.extractors)?;
+    show_entries(config, "profiles", &config.profiles)
+}
+
+/// Prints the merged entries of a section in JSON, each along with the files
+/// it was merged from.
+fn show_entries<V>(config: &Configuration, section: &str, entries: &HashMap<String, V>) -> Result<(), Box<Error>>
+where
+    V: Serialize,
+{
+    let mut names: Vec<&String> = entries.keys().collect();
+    names.sort();
+
+    println!("\n{}:", section);
+    for name in names {
+        let key = format!("{}.{}", section, name);
+        let rendered = Format::Json.render(&serde_json::to_value(&entries[name])?)?;
+
+        println!("  {} ({})", name, origins(config, &key));
+        for line in rendered.lines() {
+            println!("    {}", line);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+use super::DosboxConfig;
+
+/// A command of the `[autoexec]` section.
+///
+/// Host paths of `mount` and `imgmount` are kept as written, DOS paths are
+/// never touched.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutoexecCommand {
+    Mount {
+        drive: String,
+        path: String,
+        options: Vec<String>,
+    },
+    ImgMount {
+        drive: String,
+        paths: Vec<String>,
+        options: Vec<String>,
+    },
+    /// Switches to a drive, e.g. `C:`.
+    Drive(String),
+    Cd(String),
+    Echo {
+        silent: bool,
+        text: String,
+    },
+    /// Runs an executable or any other DOS command.
+    Call {
+        program: String,
+        args: String,
+    },
+    Empty,
+}
+
+impl AutoexecCommand {
+    pub fn parse(line: &str) -> AutoexecCommand {
+        let line = line.trim();
+        let (program, args) = match line.find(char::is_whitespace) {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line, ""),
+        };
+        let lowercase = program.to_lowercase();
+
+        if line.is_empty() {
+            return AutoexecCommand::Empty;
+        }
+
+        if lowercase == "cd" || lowercase.starts_with("cd\\") || lowercase.starts_with("cd..") {
+            let dir = format!("{} {}", &program[2..], args);
+            return AutoexecCommand::Cd(String::from(dir.trim()));
+        }
+
+        if lowercase == "echo" || lowercase == "@echo" {
+            return AutoexecCommand::Echo {
+                silent: lowercase.starts_with('@'),
+                text: String::from(args),
+            };
+        }
+
+        if program.len() == 2
+            && program.ends_with(':')
+            && args.is_empty()
+            && program
+                .chars()
+                .next()
+                .map_or(false, |c| c.is_ascii_alphabetic())
+        {
+            return AutoexecCommand::Drive(String::from(&program[..1]));
+        }
+
+        let tokens = tokenise(args);
+        let is_option = |token: &String| token.starts_with('-');
+
+        match lowercase.as_str() {
+            "mount" if tokens.len() >= 2 && !tokens[..2].iter().any(&is_option) => {
+                AutoexecCommand::Mount {
+                    drive: tokens[0].clone(),
+                    path: tokens[1].clone(),
+                    options: tokens[2..].to_vec(),
+                }
+            }
+            "imgmount" if tokens.len() >= 2 && !tokens[..2].iter().any(&is_option) => {
+                let paths_end = tokens[1..]
+                    .iter()
+                    .position(&is_option)
+                    .map_or(tokens.len(), |index| index + 1);
+
+                AutoexecCommand::ImgMount {
+                    drive: tokens[0].clone(),
+                    paths: tokens[1..paths_end].to_vec(),
+                    options: tokens[paths_end..].to_vec(),
+                }
+            }
+            _ => AutoexecCommand::Call {
+                program: String::from(program),
+                args: String::from(args),
+            },
+        }
+    }
+
+    /// Returns the host paths mounted by the command.
+    pub fn host_paths(&self) -> Vec<&str> {
+        match *self {
+            AutoexecCommand::Mount { ref path, .. } => vec![path],
+            AutoexecCommand::ImgMount { ref paths, .. } => {
+                paths.iter().map(String::as_str).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Reinterprets relative host paths as relative to `base`, normalising
+    /// them to `/` separators. Returns whether the command changed.
+    pub fn rebase(&mut self, base: &Path) -> bool {
+        let paths = match *self {
+            AutoexecCommand::Mount { ref mut path, .. } => vec![path],
+            AutoexecCommand::ImgMount { ref mut paths, .. } => paths.iter_mut().collect(),
+            _ => Vec::new(),
+        };
+
+        let mut changed = false;
+
+        for path in paths {
+            let host_path = PathBuf::from(path.replace("\\", "/"));
+
+            if host_path.is_absolute() {
+                continue;
+            }
+
+            *path = normalise(&base.join(host_path))
+                .to_string_lossy()
+                .into_owned();
+            changed = true;
+        }
+
+        changed
+    }
+}
+
+impl fmt::Display for AutoexecCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AutoexecCommand::Mount {
+                ref drive,
+                ref path,
+                ref options,
+            } => {
+                write!(f, "mount {} \"{}\"", drive, path)?;
+                write_options(f, options)
+            }
+            AutoexecCommand::ImgMount {
+                ref drive,
+                ref paths,
+                ref options,
+            } => {
+                write!(f, "imgmount {}", drive)?;
+                for path in paths {
+                    write!(f, " \"{}\"", path)?;
+                }
+                write_options(f, options)
+            }
+            AutoexecCommand::Drive(ref drive) => write!(f, "{}:", drive),
+            AutoexecCommand::Cd(ref dir) => write!(f, "cd {}", dir),
+            AutoexecCommand::Echo { silent, ref text } => {
+                write!(f, "{}echo", if silent { "@" } else { "" })?;
+                if !text.is_empty() {
+                    write!(f, " {}", text)?;
+                }
+                Ok(())
+            }
+            AutoexecCommand::Call {
+                ref program,
+                ref args,
+            } => {
+                write!(f, "{}", program)?;
+                if !args.is_empty() {
+                    write!(f, " {}", args)?;
+                }
+                Ok(())
+            }
+            AutoexecCommand::Empty => Ok(()),
+        }
+    }
+}
+
+fn write_options(f: &mut fmt::Formatter, options: &[String]) -> fmt::Result {
+    for option in options {
+        if option.contains(char::is_whitespace) {
+            write!(f, " \"{}\"", option)?;
+        } else {
+            write!(f, " {}", option)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits arguments on whitespace, keeping double quoted arguments together.
+fn tokenise(args: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut in_token = false;
+
+    for c in args.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(token.clone());
+                    token.clear();
+                    in_token = false;
+                }
+            }
+            c => {
+                token.push(c);
+                in_token = true;
+            }
+        }
+    }
+
+    if in_token {
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+fn normalise(path: &Path) -> PathBuf {
+    let mut normalised = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalised.pop() {
+                    normalised.push("..");
+                }
+            }
+            component => normalised.push(component.as_os_str()),
+        }
+    }
+
+    normalised
+}
+
+impl DosboxConfig {
+    /// Generates a config that mounts `target` as `C:`, runs `executable`,
+    /// given relative to `target`, from its own directory and exits.
+    pub fn launcher(target: &Path, executable: &Path) -> DosboxConfig {
+        let dos_dir: Vec<String> = executable
+            .parent()
+            .into_iter()
+            .flat_map(|dir| dir.components())
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+
+        let mut commands = vec![
+            AutoexecCommand::Echo {
+                silent: true,
+                text: String::from("off"),
+            },
+            AutoexecCommand::Mount {
+                drive: String::from("C"),
+                path: target.to_string_lossy().into_owned(),
+                options: Vec::new(),
+            },
+            AutoexecCommand::Drive(String::from("C")),
+        ];
+
+        if !dos_dir.is_empty() {
+            commands.push(AutoexecCommand::Cd(format!("\\{}", dos_dir.join("\\"))));
+        }
+
+        if let Some(name) = executable.file_name() {
+            commands.push(AutoexecCommand::Call {
+                program: name.to_string_lossy().into_owned(),
+                args: String::new(),
+            });
+        }
+
+        commands.push(AutoexecCommand::Call {
+            program: String::from("exit"),
+            args: String::new(),
+        });
+
+        DosboxConfig {
+            autoexec: commands.iter().map(|command| command.to_string()).collect(),
+            settings: Default::default(),
+        }
+    }
+
+    /// Parses the `[autoexec]` section into structured commands.
+    pub fn commands(&self) -> Vec<AutoexecCommand> {
+        self.autoexec
+            .iter()
+            .map(|line| AutoexecCommand::parse(line))
+            .collect()
+    }
+
+    /// Rewrites relative host paths of `mount` and `imgmount` commands to be
+    /// relative to `base`, leaving all other commands as written.
+    pub fn rebase_mounts(&mut self, base: &Path) {
+        for line in &mut self.autoexec {
+            let mut command = AutoexecCommand::parse(line);
+
+            if command.rebase(base) {
+                *line = command.to_string();
+            }
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::schema::{setting_type, Issue, IssueKind};
+use super::DosboxConfig;
+
+/// The DOSBox flavour a config is written for.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dialect {
+    /// Vanilla DOSBox 0.74, as shipped with GOG games.
+    #[serde(alias = "0.74")]
+    Dosbox,
+    #[serde(alias = "dosbox-staging")]
+    Staging,
+    #[serde(alias = "x")]
+    DosboxX,
+}
+
+/// How a dialect differs from DOSBox 0.74, the canonical dialect.
+struct DialectMap {
+    /// 0.74 settings known under another section or key.
+    renamed: &'static [((&'static str, &'static str), (&'static str, &'static str))],
+    /// 0.74 settings the dialect doesn't support.
+    removed: &'static [(&'static str, &'static str)],
+    /// Settings only the dialect supports, named as in the dialect.
+    added: &'static [(&'static str, &'static str)],
+    /// Values of 0.74 settings that the dialect spells differently, as
+    /// `(section, key, 0.74 value, dialect value)`.
+    values: &'static [(&'static str, &'static str, &'static str, &'static str)],
+    /// Dialect values that fall back to a 0.74 value, as `(section, key,
+    /// dialect value, 0.74 value)`.
+    fallbacks: &'static [(&'static str, &'static str, &'static str, &'static str)],
+}
+
+const DOSBOX: DialectMap = DialectMap {
+    renamed: &[],
+    removed: &[],
+    added: &[],
+    values: &[],
+    fallbacks: &[],
+};
+
+const STAGING: DialectMap = DialectMap {
+    renamed: &[
+        (("dosbox", "captures"), ("capture", "capture_dir")),
+        (("sdl", "sensitivity"), ("mouse", "mouse_sensitivity")),
+    ],
+    removed: &[
+        ("sdl", "fulldouble"),
+        ("sdl", "usescancodes"),
+        ("render", "scaler"),
+        ("sblaster", "oplemu"),
+    ],
+    added: &[
+        ("sdl", "texture_renderer"),
+        ("sdl", "vsync"),
+        ("dosbox", "startup_verbosity"),
+        ("render", "glshader"),
+        ("mixer", "reverb"),
+        ("mixer", "chorus"),
+        ("speaker", "zero_offset"),
+        ("dos", "ver"),
+    ],
+    values: &[
+        ("sdl", "output", "overlay", "texture"),
+        ("sdl", "output", "ddraw", "texture"),
+    ],
+    fallbacks: &[
+        ("sdl", "output", "texture", "surface"),
+        ("sdl", "output", "texturenb", "surface"),
+    ],
+};
+
+const DOSBOX_X: DialectMap = DialectMap {
+    renamed: &[],
+    removed: &[],
+    added: &[
+        ("sdl", "windowposition"),
+        ("dosbox", "title"),
+        ("dosbox", "fastbioslogo"),
+        ("dosbox", "startbanner"),
+        ("video", "vmemsize"),
+        ("render", "glshader"),
+        ("cpu", "turbo"),
+        ("mixer", "swapstereo"),
+        ("dos", "lfn"),
+        ("dos", "ver"),
+    ],
+    values: &[],
+    fallbacks: &[
+        ("dosbox", "machine", "svga_s3trio64", "svga_s3"),
+        ("dosbox", "machine", "svga_s3trio64v+", "svga_s3"),
+        ("dosbox", "machine", "svga_s3vision864", "svga_s3"),
+        ("dosbox", "machine", "svga_s3vision868", "svga_s3"),
+        ("dosbox", "machine", "svga_s386c928", "svga_s3"),
+        ("sdl", "output", "direct3d", "surface"),
+    ],
+};
+
+const DIALECTS: &[Dialect] = &[Dialect::Dosbox, Dialect::Staging, Dialect::DosboxX];
+
+fn matches(a: (&str, &str), b: (&str, &str)) -> bool {
+    a.0.eq_ignore_ascii_case(b.0) && a.1.eq_ignore_ascii_case(b.1)
+}
+
+impl Dialect {
+    fn map(&self) -> &'static DialectMap {
+        match *self {
+            Dialect::Dosbox => &DOSBOX,
+            Dialect::Staging => &STAGING,
+            Dialect::DosboxX => &DOSBOX_X,
+        }
+    }
+
+    /// Returns the 0.74 name of a setting written in this dialect.
+    fn canonical<'a>(&self, section: &'a str, key: &'a str) -> (&'a str, &'a str) {
+        self.map()
+            .renamed
+            .iter()
+            .find(|&&(_, renamed)| matches(renamed, (section, key)))
+            .map_or((section, key), |&(canonical, _)| canonical)
+    }
+
+    /// Returns the name of a 0.74 setting in this dialect.
+    fn rename<'a>(&self, section: &'a str, key: &'a str) -> (&'a str, &'a str) {
+        self.map()
+            .renamed
+            .iter()
+            .find(|&&(canonical, _)| matches(canonical, (section, key)))
+            .map_or((section, key), |&(_, renamed)| renamed)
+    }
+
+    /// Whether the setting, named as in this dialect, is supported by it.
+    pub fn supports(&self, section: &str, key: &str) -> bool {
+        let map = self.map();
+        let canonical = self.canonical(section, key);
+
+        map.added
+            .iter()
+            .any(|&added| matches(added, (section, key)))
+            || (setting_type(canonical.0, canonical.1).is_some()
+                && !map
+                    .removed
+                    .iter()
+                    .any(|&removed| matches(removed, canonical)))
+    }
+
+    /// Whether the value is specific to this dialect.
+    fn has_value(&self, section: &str, key: &str, value: &str) -> bool {
+        let map = self.map();
+
+        map.values
+            .iter()
+            .map(|&(s, k, _, dialect_value)| (s, k, dialect_value))
+            .chain(
+                map.fallbacks
+                    .iter()
+                    .map(|&(s, k, dialect_value, _)| (s, k, dialect_value)),
+            )
+            .any(|(s, k, dialect_value)| {
+                matches((s, k), (section, key)) && dialect_value.eq_ignore_ascii_case(value)
+            })
+    }
+
+    /// Whether any dialect knows the setting, named as in 0.74 or as a
+    /// dialect-only setting.
+    fn is_known(section: &str, key: &str) -> bool {
+        setting_type(section, key).is_some()
+            || DIALECTS.iter().any(|dialect| {
+                dialect
+                    .map()
+                    .added
+                    .iter()
+                    .any(|&added| matches(added, (section, key)))
+            })
+    }
+
+    /// Guesses the dialect of a config from the settings that 0.74 doesn't
+    /// have, preferring Staging on ties and defaulting to 0.74.
+    pub fn detect(config: &DosboxConfig) -> Dialect {
+        DIALECTS
+            .iter()
+            .skip(1)
+            .map(|dialect| {
+                let map = dialect.map();
+                let score = config
+                    .settings
+                    .iter()
+                    .flat_map(|(section, settings)| settings.keys().map(move |key| (section, key)))
+                    .filter(|&(section, key)| {
+                        map.added
+                            .iter()
+                            .chain(map.renamed.iter().map(|pair| &pair.1))
+                            .any(|&name| matches(name, (section, key)))
+                    })
+                    .count();
+
+                (*dialect, score)
+            })
+            .fold((Dialect::Dosbox, 0), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            })
+            .0
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect::Dosbox
+    }
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Dialect, String> {
+        match s {
+            "dosbox" | "0.74" => Ok(Dialect::Dosbox),
+            "staging" | "dosbox-staging" => Ok(Dialect::Staging),
+            "x" | "dosbox-x" => Ok(Dialect::DosboxX),
+            _ => Err(format!(
+                "unknown dialect '{}', expected dosbox, staging or dosbox-x",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Dialect::Dosbox => write!(f, "DOSBox 0.74"),
+            Dialect::Staging => write!(f, "DOSBox Staging"),
+            Dialect::DosboxX => write!(f, "DOSBox-X"),
+        }
+    }
+}
+
+impl DosboxConfig {
+    /// Validates a config written in a dialect, checking 0.74 settings
+    /// against the schema and reporting those the dialect doesn't support.
+    pub fn validate_as(&self, dialect: Dialect) -> Vec<Issue> {
+        let mut issues: Vec<Issue> = self
+            .validate()
+            .into_iter()
+            .filter(|issue| match issue.kind {
+                IssueKind::InvalidValue(_) => {
+                    !dialect.has_value(&issue.section, &issue.key, &issue.value)
+                }
+                _ => !(issue.is_unknown() && dialect.supports(&issue.section, &issue.key)),
+            })
+            .collect();
+
+        for (section, settings) in &self.settings {
+            for (key, value) in settings {
+                if setting_type(section, key).is_some() && !dialect.supports(section, key) {
+                    issues.push(Issue {
+                        section: section.clone(),
+                        key: key.clone(),
+                        value: value.clone(),
+                        kind: IssueKind::Unsupported(dialect),
+                    });
+                }
+            }
+        }
+
+        issues.sort_by(|a, b| (&a.section, &a.key).cmp(&(&b.section, &b.key)));
+        issues
+    }
+
+    /// Translates a config between dialects, renaming settings and values.
+    /// Settings that are known but unsupported by the target dialect are
+    /// dropped and reported, unknown settings are kept as they are.
+    pub fn translate(&self, from: Dialect, to: Dialect) -> (DosboxConfig, Vec<Issue>) {
+        if from == to {
+            return (self.clone(), Vec::new());
+        }
+
+        let mut translated = DosboxConfig {
+            autoexec: self.autoexec.clone(),
+            settings: HashMap::new(),
+        };
+        let mut issues = Vec::new();
+
+        for (section, settings) in &self.settings {
+            for (key, value) in settings {
+                let canonical = from.canonical(section, key);
+                let canonical_value = from
+                    .map()
+                    .fallbacks
+                    .iter()
+                    .cloned()
+                    .chain(from.map().values.iter().map(
+                        |&(s, k, canonical_value, dialect_value)| {
+                            (s, k, dialect_value, canonical_value)
+                        },
+                    ))
+                    .find(|&(s, k, dialect_value, _)| {
+                        matches((s, k), canonical) && dialect_value.eq_ignore_ascii_case(value)
+                    })
+                    .map_or(value.as_str(), |(_, _, _, canonical_value)| canonical_value);
+
+                let (target_section, target_key) = to.rename(canonical.0, canonical.1);
+
+                if Dialect::is_known(canonical.0, canonical.1)
+                    && !to.supports(target_section, target_key)
+                {
+                    issues.push(Issue {
+                        section: section.clone(),
+                        key: key.clone(),
+                        value: value.clone(),
+                        kind: IssueKind::Unsupported(to),
+                    });
+                    continue;
+                }
+
+                let target_value = to
+                    .map()
+                    .values
+                    .iter()
+                    .find(|&&(s, k, value, _)| {
+                        matches((s, k), canonical) && value.eq_ignore_ascii_case(canonical_value)
+                    })
+                    .map_or(canonical_value, |&(_, _, _, dialect_value)| dialect_value);
+
+                translated
+                    .settings
+                    .entry(String::from(target_section))
+                    .or_insert_with(HashMap::new)
+                    .insert(String::from(target_key), String::from(target_value));
+            }
+        }
+
+        issues.sort_by(|a, b| (&a.section, &a.key).cmp(&(&b.section, &b.key)));
+        (translated, issues)
+    }
+}
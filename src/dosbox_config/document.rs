@@ -0,0 +1,593 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use super::DosboxConfig;
+
+/// A single line of a DOSBox config, keeping its original text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Line {
+    Blank(String),
+    Comment(String),
+    Section {
+        name: String,
+        raw: String,
+    },
+    Setting {
+        section: String,
+        key: String,
+        raw: String,
+        value: Range<usize>,
+    },
+    Command(String),
+}
+
+impl Line {
+    pub fn raw(&self) -> &str {
+        match *self {
+            Line::Blank(ref raw)
+            | Line::Comment(ref raw)
+            | Line::Command(ref raw)
+            | Line::Section { ref raw, .. }
+            | Line::Setting { ref raw, .. } => raw,
+        }
+    }
+
+    fn section(&self) -> Option<&str> {
+        match *self {
+            Line::Section { ref name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    fn is_section(&self, section: &str) -> bool {
+        self.section()
+            .map_or(false, |name| name.eq_ignore_ascii_case(section))
+    }
+
+    fn is_setting(&self, section: &str, key: &str) -> bool {
+        match *self {
+            Line::Setting {
+                section: ref line_section,
+                key: ref line_key,
+                ..
+            } => line_section.eq_ignore_ascii_case(section) && line_key.eq_ignore_ascii_case(key),
+            _ => false,
+        }
+    }
+}
+
+/// A malformed line, with 1-based line and column numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {
+    fn description(&self) -> &str {
+        "malformed dosbox config"
+    }
+}
+
+/// Returns the 1-based column of a byte offset.
+fn column(raw: &str, offset: usize) -> usize {
+    raw[..offset].chars().count() + 1
+}
+
+/// Strips an inline comment, a `#` at the start or preceded by whitespace.
+fn strip_comment(text: &str) -> &str {
+    let comment = text.char_indices().find(|&(index, c)| {
+        c == '#'
+            && text[..index]
+                .chars()
+                .last()
+                .map_or(true, char::is_whitespace)
+    });
+
+    match comment {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
+
+/// A DOSBox config that keeps comments, blank lines, ordering and formatting,
+/// so that it can be edited and written back with minimal changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    lines: Vec<Line>,
+    line_ending: String,
+}
+
+impl Document {
+    pub fn read(path: &PathBuf) -> Result<Document, Box<Error>> {
+        let mut text = String::new();
+        File::open(&path)?.read_to_string(&mut text)?;
+
+        let lines: Vec<&str> = text.lines().collect();
+        let mut document = Document::parse(lines.as_slice())?;
+
+        if text.contains("\r\n") {
+            document.line_ending = String::from("\r\n");
+        }
+
+        Ok(document)
+    }
+
+    /// Tokenises a config line by line. Sections and keys keep their case
+    /// but are compared case-insensitively, `#` and `%` start comments and
+    /// malformed lines are reported as a `ParseError`.
+    pub fn parse<T>(lines: &[T]) -> Result<Document, Box<Error>>
+    where
+        T: AsRef<str>,
+    {
+        let mut section: Option<String> = None;
+        let mut parsed = Vec::new();
+
+        for (index, raw) in lines
+            .iter()
+            .map(|line| line.as_ref().trim_right_matches('\r'))
+            .enumerate()
+        {
+            let line = Document::tokenise(raw, section.as_ref().map(String::as_str)).map_err(
+                |(column, message)| ParseError {
+                    line: index + 1,
+                    column: column,
+                    message: message,
+                },
+            )?;
+
+            if let Some(name) = line.section() {
+                section = Some(String::from(name));
+            }
+
+            parsed.push(line);
+        }
+
+        Ok(Document {
+            lines: parsed,
+            line_ending: String::from("\n"),
+        })
+    }
+
+    /// Tokenises a single line, returning the column and a description of
+    /// the problem when it is malformed.
+    fn tokenise(raw: &str, section: Option<&str>) -> Result<Line, (usize, String)> {
+        let line = raw.trim();
+        let indent = raw.len() - raw.trim_left().len();
+        let autoexec = section.map_or(false, |name| name.eq_ignore_ascii_case("autoexec"));
+
+        if line.is_empty() {
+            return Ok(Line::Blank(String::from(raw)));
+        }
+
+        if line.starts_with('#') || (line.starts_with('%') && !autoexec) {
+            return Ok(Line::Comment(String::from(raw)));
+        }
+
+        if line.starts_with('[') {
+            let header = strip_comment(line).trim_right();
+
+            if !header.ends_with(']') {
+                return Err((
+                    column(raw, indent + header.len()),
+                    String::from("expected ']' after the section name"),
+                ));
+            }
+
+            let name = header[1..header.len() - 1].trim();
+            if name.is_empty() {
+                return Err((
+                    column(raw, indent) + 1,
+                    String::from("expected a section name"),
+                ));
+            }
+
+            return Ok(Line::Section {
+                name: String::from(name),
+                raw: String::from(raw),
+            });
+        }
+
+        match section {
+            Some(_) if autoexec => Ok(Line::Command(String::from(raw))),
+            Some(section) => Document::setting_from(section, raw),
+            None => Err((
+                column(raw, indent),
+                String::from("expected a section header before the first setting"),
+            )),
+        }
+    }
+
+    fn setting_from(section: &str, raw: &str) -> Result<Line, (usize, String)> {
+        let separator = match raw.find('=') {
+            Some(separator) => separator,
+            None => {
+                let end = raw.trim_right().len();
+                return Err((column(raw, end), String::from("expected '=' after the key")));
+            }
+        };
+
+        let key = raw[..separator].trim();
+        if key.is_empty() {
+            return Err((
+                column(raw, separator),
+                String::from("expected a key before '='"),
+            ));
+        }
+
+        let key_start = raw.len() - raw.trim_left().len();
+        if let Some(space) = key.find(char::is_whitespace) {
+            return Err((
+                column(raw, key_start + space),
+                String::from("unexpected whitespace in the key"),
+            ));
+        }
+
+        let rest = strip_comment(&raw[separator + 1..]);
+        let value_text = rest.trim();
+        let value_start = separator + 1 + rest.len() - rest.trim_left().len();
+
+        Ok(Line::Setting {
+            section: String::from(section),
+            key: String::from(key),
+            raw: String::from(raw),
+            value: value_start..value_start + value_text.len(),
+        })
+    }
+
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+
+    /// Returns the value of a setting, the last occurrence winning.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.lines
+            .iter()
+            .rev()
+            .filter(|line| line.is_setting(section, key))
+            .filter_map(|line| match *line {
+                Line::Setting {
+                    ref raw, ref value, ..
+                } => Some(&raw[value.clone()]),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Returns the line numbers and values of every occurrence of a setting.
+    pub fn occurrences(&self, section: &str, key: &str) -> Vec<(usize, &str)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|&(_, line)| line.is_setting(section, key))
+            .filter_map(|(index, line)| match *line {
+                Line::Setting {
+                    ref raw, ref value, ..
+                } => Some((index + 1, &raw[value.clone()])),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the settings that occur more than once, as their section, key
+    /// and the line numbers of every occurrence.
+    pub fn duplicates(&self) -> Vec<(String, String, Vec<usize>)> {
+        let mut duplicates: Vec<(String, String, Vec<usize>)> = Vec::new();
+
+        for line in &self.lines {
+            if let Line::Setting {
+                ref section,
+                ref key,
+                ..
+            } = *line
+            {
+                let seen = duplicates.iter().any(|&(ref s, ref k, _)| {
+                    s.eq_ignore_ascii_case(section) && k.eq_ignore_ascii_case(key)
+                });
+                let lines: Vec<usize> = self
+                    .occurrences(section, key)
+                    .iter()
+                    .map(|&(line, _)| line)
+                    .collect();
+
+                if !seen && lines.len() > 1 {
+                    duplicates.push((section.to_lowercase(), key.to_lowercase(), lines));
+                }
+            }
+        }
+
+        duplicates
+    }
+
+    /// Sets the value of a setting, rewriting only the value of an existing
+    /// line, or adding the setting to the end of its section. Returns whether
+    /// the document changed.
+    pub fn set(&mut self, section: &str, key: &str, val: &str) -> bool {
+        let existing = self
+            .lines
+            .iter()
+            .rposition(|line| line.is_setting(section, key));
+
+        if let Some(index) = existing {
+            if let Line::Setting {
+                ref mut raw,
+                ref mut value,
+                ..
+            } = self.lines[index]
+            {
+                if &raw[value.clone()] == val {
+                    return false;
+                }
+
+                raw.replace_range(value.clone(), val);
+                *value = value.start..value.start + val.len();
+            }
+            return true;
+        }
+
+        let (indent, separator) = self.setting_format(section);
+        let raw = format!("{}{}{}{}", indent, key, separator, val);
+        let value_start = raw.len() - val.len();
+        let line = Line::Setting {
+            section: String::from(section),
+            key: String::from(key),
+            raw: raw,
+            value: value_start..value_start + val.len(),
+        };
+
+        let index = match self.section_range(section) {
+            Some(range) => self.lines[range.clone()]
+                .iter()
+                .rposition(|line| match *line {
+                    Line::Setting { .. } => true,
+                    _ => false,
+                })
+                .map_or(range.start, |last| range.start + last + 1),
+            None => self.insert_section(section),
+        };
+
+        self.lines.insert(index, line);
+        true
+    }
+
+    /// Removes every occurrence of a setting. Returns whether the document
+    /// changed.
+    pub fn unset(&mut self, section: &str, key: &str) -> bool {
+        let len = self.lines.len();
+
+        self.lines.retain(|line| !line.is_setting(section, key));
+
+        self.lines.len() != len
+    }
+
+    /// Replaces the commands of the `[autoexec]` section, keeping its
+    /// comments. Returns whether the document changed.
+    pub fn set_autoexec(&mut self, commands: &[String]) -> bool {
+        let range = match self.section_range("autoexec") {
+            Some(range) => range,
+            None => {
+                let index = self.insert_section("autoexec");
+                index..index
+            }
+        };
+
+        let current: Vec<&str> = self.lines[range.clone()]
+            .iter()
+            .filter_map(|line| match *line {
+                Line::Command(ref raw) => Some(raw.as_str()),
+                _ => None,
+            })
+            .collect();
+
+        if current == commands.iter().map(String::as_str).collect::<Vec<&str>>() {
+            return false;
+        }
+
+        let existing: Vec<usize> = range
+            .clone()
+            .filter(|&index| match self.lines[index] {
+                Line::Command(_) => true,
+                _ => false,
+            })
+            .collect();
+
+        let insert_at = existing.first().cloned().unwrap_or(range.end);
+
+        for &index in existing.iter().rev() {
+            self.lines.remove(index);
+        }
+
+        for (offset, command) in commands.iter().enumerate() {
+            self.lines
+                .insert(insert_at + offset, Line::Command(command.clone()));
+        }
+
+        true
+    }
+
+    /// Returns the range of lines following a section header up to the next
+    /// section.
+    fn section_range(&self, section: &str) -> Option<Range<usize>> {
+        let header = self
+            .lines
+            .iter()
+            .position(|line| line.is_section(section))?;
+
+        let end = self.lines[header + 1..]
+            .iter()
+            .position(|line| line.section().is_some())
+            .map_or(self.lines.len(), |next| header + 1 + next);
+
+        Some(header + 1..end)
+    }
+
+    /// Adds a section header before `[autoexec]`, or at the end, returning
+    /// the index where its first line belongs.
+    fn insert_section(&mut self, section: &str) -> usize {
+        let mut index = if section.eq_ignore_ascii_case("autoexec") {
+            self.lines.len()
+        } else {
+            self.lines
+                .iter()
+                .position(|line| line.is_section("autoexec"))
+                .unwrap_or_else(|| self.lines.len())
+        };
+
+        let previous_blank = index == 0
+            || match self.lines[index - 1] {
+                Line::Blank(_) => true,
+                _ => false,
+            };
+
+        if !previous_blank {
+            self.lines.insert(index, Line::Blank(String::new()));
+            index += 1;
+        }
+
+        self.lines.insert(
+            index,
+            Line::Section {
+                name: String::from(section),
+                raw: format!("[{}]", section),
+            },
+        );
+
+        if index + 1 < self.lines.len() {
+            self.lines.insert(index + 1, Line::Blank(String::new()));
+        }
+
+        index + 1
+    }
+
+    /// Mirrors the indentation and separator of existing settings, preferring
+    /// those of the same section.
+    fn setting_format(&self, section: &str) -> (String, String) {
+        let mut settings = self.lines.iter().filter_map(|line| match *line {
+            Line::Setting {
+                section: ref line_section,
+                ref key,
+                ref raw,
+                ref value,
+            } => Some((line_section.eq_ignore_ascii_case(section), key, raw, value)),
+            _ => None,
+        });
+
+        let template = settings
+            .clone()
+            .filter(|&(same_section, ..)| same_section)
+            .last()
+            .or_else(|| settings.next());
+
+        match template {
+            Some((_, key, raw, value)) => {
+                let key_start = raw.find(key.as_str()).unwrap_or(0);
+                (
+                    String::from(&raw[..key_start]),
+                    String::from(&raw[key_start + key.len()..value.start.min(raw.len())]),
+                )
+            }
+            None => (String::new(), String::from("=")),
+        }
+    }
+
+    /// Returns the semantic view of the document, with lowercase section and
+    /// key names and the last duplicate winning.
+    pub fn config(&self) -> DosboxConfig {
+        let mut config = DosboxConfig::default();
+
+        for line in &self.lines {
+            match *line {
+                Line::Command(ref raw) => config.autoexec.push(String::from(raw.trim())),
+                Line::Setting {
+                    ref section,
+                    ref key,
+                    ref raw,
+                    ref value,
+                } => {
+                    config
+                        .settings
+                        .entry(section.to_lowercase())
+                        .or_insert_with(Default::default)
+                        .insert(key.to_lowercase(), String::from(&raw[value.clone()]));
+                }
+                _ => {}
+            }
+        }
+
+        config
+    }
+
+    /// Updates the document to match a config, rewriting only the settings
+    /// whose values changed and the autoexec commands if they differ.
+    /// Settings the config doesn't have are left as they are.
+    pub fn apply(&mut self, config: &DosboxConfig) {
+        let mut sections: Vec<&String> = config.settings.keys().collect();
+        sections.sort();
+
+        for section in sections {
+            let settings = &config.settings[section];
+            let mut keys: Vec<&String> = settings.keys().collect();
+            keys.sort();
+
+            for key in keys {
+                self.set(section, key, &settings[key]);
+            }
+        }
+
+        if self.config().autoexec != config.autoexec {
+            self.set_autoexec(&config.autoexec);
+        }
+    }
+
+    /// Applies a config translated from the document's own settings, also
+    /// removing the settings it doesn't have so that those renamed or dropped
+    /// by the translation don't linger under their old names.
+    pub fn apply_translated(&mut self, config: &DosboxConfig) {
+        for (section, settings) in self.config().settings {
+            for key in settings.keys() {
+                let keep = config
+                    .settings
+                    .get(&section)
+                    .map_or(false, |settings| settings.contains_key(key));
+
+                if !keep {
+                    self.unset(&section, key);
+                }
+            }
+        }
+
+        self.apply(config);
+    }
+
+    pub fn write(&self, path: &PathBuf) -> Result<(), Box<Error>> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_string().as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            write!(f, "{}{}", line.raw(), self.line_ending)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serializer};
+use serde_json;
+use toml;
+
+use super::DosboxConfig;
+
+/// The formats a DOSBox config can be read from and written to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// The native DOSBox `.conf` format.
+    Conf,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// Picks the format from the extension of a path, defaulting to `Conf`.
+    pub fn from_path<T>(path: &T) -> Format
+    where
+        T: AsRef<Path>,
+    {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| extension.to_lowercase().parse().ok())
+            .unwrap_or(Format::Conf)
+    }
+}
+
+impl Default for Format {
+    fn default() -> Format {
+        Format::Conf
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "conf" => Ok(Format::Conf),
+            "json" => Ok(Format::Json),
+            "toml" => Ok(Format::Toml),
+            _ => Err(format!(
+                "unknown format '{}', expected conf, json or toml",
+                s
+            )),
+        }
+    }
+}
+
+/// Serialises the settings with sections and keys in order, so that exports
+/// are stable.
+pub fn ordered_settings<S>(
+    settings: &HashMap<String, HashMap<String, String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let ordered: BTreeMap<&String, BTreeMap<&String, &String>> = settings
+        .iter()
+        .map(|(section, settings)| (section, settings.iter().collect()))
+        .collect();
+
+    serializer.collect_map(ordered)
+}
+
+/// Deserialises the settings with lowercase section and key names, as
+/// parsing a `.conf` does, so that configs from every source merge alike.
+/// Of the names differing only in case, the last in order wins.
+pub fn lowercase_settings<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, HashMap<String, String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let ordered: BTreeMap<String, BTreeMap<String, String>> = Deserialize::deserialize(deserializer)?;
+    let mut settings: HashMap<String, HashMap<String, String>> = HashMap::new();
+
+    for (section, values) in ordered {
+        settings
+            .entry(section.to_lowercase())
+            .or_insert_with(HashMap::new)
+            .extend(values.into_iter().map(|(key, val)| (key.to_lowercase(), val)));
+    }
+
+    Ok(settings)
+}
+
+impl DosboxConfig {
+    pub fn import(text: &str, format: Format) -> Result<DosboxConfig, Box<Error>> {
+        match format {
+            Format::Conf => {
+                let lines: Vec<&str> = text.lines().collect();
+                DosboxConfig::parse(lines.as_slice())
+            }
+            Format::Json => Ok(serde_json::from_str(text)?),
+            Format::Toml => Ok(toml::from_str(text)?),
+        }
+    }
+
+    /// Renders the config, with sections and keys in order.
+    pub fn export(&self, format: Format) -> Result<String, Box<Error>> {
+        match format {
+            Format::Conf => {
+                let mut text = String::new();
+                let mut sections: Vec<&String> = self.settings.keys().collect();
+                sections.sort();
+
+                for section in sections {
+                    text.push_str(&format!("[{}]\n", section));
+
+                    let settings = &self.settings[section];
+                    let mut keys: Vec<&String> = settings.keys().collect();
+                    keys.sort();
+
+                    for key in keys {
+                        text.push_str(&format!("{}={}\n", key, settings[key]));
+                    }
+                }
+
+                text.push_str("[autoexec]\n");
+                for line in &self.autoexec {
+                    text.push_str(&format!("{}\n", line));
+                }
+
+                Ok(text)
+            }
+            Format::Json => Ok(serde_json::to_string_pretty(self)? + "\n"),
+            Format::Toml => Ok(toml::to_string(self)?),
+        }
+    }
+}
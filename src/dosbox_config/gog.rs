@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+
+/// The part a config plays in a GOG DOSBox release, which layers a game or
+/// setup config over a shared base config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GogRole {
+    /// `dosbox*.conf`, the settings shared by the game and its setup.
+    Base,
+    /// `*_single.conf` or `*_client.conf`, mounting and starting the game.
+    Game,
+    /// `*_setup.conf`, starting the sound setup.
+    Setup,
+}
+
+/// Returns the lowercase file name of a config without its `.conf` extension.
+fn conf_stem(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+
+    if name.ends_with(".conf") {
+        Some(String::from(&name[..name.len() - 5]))
+    } else {
+        None
+    }
+}
+
+impl GogRole {
+    pub fn of(path: &Path) -> Option<GogRole> {
+        let stem = conf_stem(path)?;
+
+        if stem.ends_with("_single") || stem.ends_with("_client") {
+            Some(GogRole::Game)
+        } else if stem.ends_with("_setup") {
+            Some(GogRole::Setup)
+        } else if stem.starts_with("dosbox") {
+            Some(GogRole::Base)
+        } else {
+            None
+        }
+    }
+}
+
+/// Finds the base config of a game or setup config among the given files:
+/// the one named like it without the suffix, or else the only base config
+/// next to it.
+pub fn gog_base<'a>(overlay: &Path, files: &'a [PathBuf]) -> Option<&'a PathBuf> {
+    let stem = conf_stem(overlay)?;
+    let base_stem = &stem[..stem.rfind('_')?];
+
+    let siblings: Vec<&PathBuf> = files
+        .iter()
+        .filter(|file| file.parent() == overlay.parent())
+        .filter(|file| GogRole::of(file) == Some(GogRole::Base))
+        .collect();
+
+    siblings
+        .iter()
+        .find(|file| conf_stem(file).map_or(false, |stem| stem == base_stem))
+        .or_else(|| if siblings.len() == 1 { siblings.first() } else { None })
+        .cloned()
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use super::DosboxConfig;
+
+/// How the settings of a section in the merged config are combined with the
+/// same section of the source config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SectionStrategy {
+    /// Settings of the merged config override those of the source, key by key.
+    Override,
+    /// Settings of the source are kept, only missing keys are added.
+    KeepSource,
+    /// The section of the merged config replaces the source section entirely.
+    ReplaceSection,
+}
+
+impl Default for SectionStrategy {
+    fn default() -> SectionStrategy {
+        SectionStrategy::Override
+    }
+}
+
+impl FromStr for SectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SectionStrategy, String> {
+        match s {
+            "override" => Ok(SectionStrategy::Override),
+            "keep-source" => Ok(SectionStrategy::KeepSource),
+            "replace-section" => Ok(SectionStrategy::ReplaceSection),
+            _ => Err(format!(
+                "unknown section strategy '{}', expected override, keep-source or replace-section",
+                s
+            )),
+        }
+    }
+}
+
+/// How the autoexec commands of the merged config are combined with those of
+/// the source config.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoexecMode {
+    Append,
+    Prepend,
+    /// Uses the merged commands instead of the source commands, unless there
+    /// are none.
+    Replace,
+    /// Appends, skipping commands that already appear, ignoring case.
+    Dedupe,
+}
+
+impl Default for AutoexecMode {
+    fn default() -> AutoexecMode {
+        AutoexecMode::Append
+    }
+}
+
+impl FromStr for AutoexecMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<AutoexecMode, String> {
+        match s {
+            "append" => Ok(AutoexecMode::Append),
+            "prepend" => Ok(AutoexecMode::Prepend),
+            "replace" => Ok(AutoexecMode::Replace),
+            "dedupe" => Ok(AutoexecMode::Dedupe),
+            _ => Err(format!(
+                "unknown autoexec mode '{}', expected append, prepend, replace or dedupe",
+                s
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MergeOptions {
+    /// The strategy for sections without one of their own.
+    pub strategy: SectionStrategy,
+    pub sections: HashMap<String, SectionStrategy>,
+    pub autoexec: AutoexecMode,
+}
+
+impl MergeOptions {
+    /// Returns the strategy of a section, whose name is matched ignoring case.
+    pub fn strategy(&self, section: &str) -> SectionStrategy {
+        self.sections
+            .iter()
+            .find(|&(name, _)| name.eq_ignore_ascii_case(section))
+            .map_or(self.strategy, |(_, &strategy)| strategy)
+    }
+}
+
+impl DosboxConfig {
+    /// Merges another config into this one using the given strategies.
+    pub fn merge_with(&self, config_b: &DosboxConfig, options: &MergeOptions) -> DosboxConfig {
+        let mut merged = self.clone();
+
+        merged.autoexec = match options.autoexec {
+            AutoexecMode::Append => self
+                .autoexec
+                .iter()
+                .chain(config_b.autoexec.iter())
+                .cloned()
+                .collect(),
+            AutoexecMode::Prepend => config_b
+                .autoexec
+                .iter()
+                .chain(self.autoexec.iter())
+                .cloned()
+                .collect(),
+            AutoexecMode::Replace if config_b.autoexec.is_empty() => self.autoexec.clone(),
+            AutoexecMode::Replace => config_b.autoexec.clone(),
+            AutoexecMode::Dedupe => {
+                let mut autoexec: Vec<String> = Vec::new();
+
+                for line in self.autoexec.iter().chain(config_b.autoexec.iter()) {
+                    let normalised = line.trim().to_lowercase();
+
+                    if !autoexec
+                        .iter()
+                        .any(|existing| existing.trim().to_lowercase() == normalised)
+                    {
+                        autoexec.push(line.clone());
+                    }
+                }
+
+                autoexec
+            }
+        };
+
+        for (section, settings) in &config_b.settings {
+            let msettings = merged
+                .settings
+                .entry(section.clone())
+                .or_insert_with(HashMap::new);
+
+            match options.strategy(section) {
+                SectionStrategy::Override => {
+                    for (key, val) in settings {
+                        msettings.insert(key.clone(), val.clone());
+                    }
+                }
+                SectionStrategy::KeepSource => {
+                    for (key, val) in settings {
+                        msettings.entry(key.clone()).or_insert_with(|| val.clone());
+                    }
+                }
+                SectionStrategy::ReplaceSection => *msettings = settings.clone(),
+            }
+        }
+
+        merged
+    }
+}
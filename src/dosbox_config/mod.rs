@@ -0,0 +1,70 @@
+mod autoexec;
+mod dialect;
+mod document;
+mod format;
+mod gog;
+mod merge;
+mod schema;
+
+pub use self::autoexec::AutoexecCommand;
+pub use self::dialect::Dialect;
+pub use self::document::{Document, Line, ParseError};
+pub use self::format::Format;
+pub use self::gog::{gog_base, GogRole};
+pub use self::merge::{AutoexecMode, MergeOptions, SectionStrategy};
+pub use self::schema::{setting_type, Issue, IssueKind, SettingType, SCHEMA};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+
+#[derive(Default, Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DosboxConfig {
+    pub autoexec: Vec<String>,
+
+    #[serde(
+        serialize_with = "format::ordered_settings",
+        deserialize_with = "format::lowercase_settings"
+    )]
+    pub settings: HashMap<String, HashMap<String, String>>,
+}
+
+impl DosboxConfig {
+    /// Reads a config in the format given by the extension of the path.
+    pub fn read(path: &PathBuf) -> Result<DosboxConfig, Box<Error>> {
+        let mut text = String::new();
+        File::open(&path)?.read_to_string(&mut text)?;
+
+        DosboxConfig::import(&text, Format::from_path(path))
+    }
+
+    pub fn parse<T>(lines: &[T]) -> Result<DosboxConfig, Box<Error>>
+    where
+        T: AsRef<str>,
+    {
+        Ok(Document::parse(lines)?.config())
+    }
+
+    /// Returns the value of a setting, ignoring the case of the section and
+    /// key as DOSBox does.
+    pub fn get(&self, section: &str, key: &str) -> Option<&String> {
+        self.settings
+            .get(&section.to_lowercase())
+            .and_then(|settings| settings.get(&key.to_lowercase()))
+    }
+
+    pub fn merge(&self, config_b: &DosboxConfig) -> DosboxConfig {
+        self.merge_with(config_b, &MergeOptions::default())
+    }
+
+    /// Writes the config in the format given by the extension of the path.
+    pub fn write(&self, path: &PathBuf) -> Result<(), Box<Error>> {
+        let mut file = File::create(path)?;
+        file.write_all(self.export(Format::from_path(path))?.as_bytes())?;
+        file.flush()?;
+        Ok(())
+    }
+}
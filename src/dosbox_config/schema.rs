@@ -0,0 +1,429 @@
+use std::fmt;
+
+use super::dialect::Dialect;
+use super::DosboxConfig;
+
+/// The type of value a DOSBox setting accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingType {
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    Enum(&'static [&'static str]),
+    /// A scaler name, optionally followed by `forced`.
+    Scaler(&'static [&'static str]),
+    /// `auto`, `max` or `fixed` modes with cycle counts, percentages and limits.
+    Cycles,
+    Text,
+}
+
+impl SettingType {
+    /// Checks a value, describing the problem when it is not accepted.
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+
+        match *self {
+            SettingType::Bool => match value.to_lowercase().as_str() {
+                "true" | "false" | "1" | "0" | "on" | "off" | "enabled" | "disabled" => Ok(()),
+                _ => Err(String::from("expected true or false")),
+            },
+            SettingType::Int { min, max } => match value.parse::<i64>() {
+                Ok(val) if val >= min && val <= max => Ok(()),
+                Ok(_) => Err(format!("expected a number from {} to {}", min, max)),
+                Err(_) => Err(String::from("expected a number")),
+            },
+            SettingType::Enum(values) => {
+                if values.iter().any(|val| val.eq_ignore_ascii_case(value)) {
+                    Ok(())
+                } else {
+                    Err(format!("expected one of: {}", values.join(", ")))
+                }
+            }
+            SettingType::Scaler(values) => {
+                let tokens: Vec<&str> = value.split_whitespace().collect();
+                let valid = match tokens.as_slice() {
+                    [name] => SettingType::Enum(values).check(name).is_ok(),
+                    [name, forced] => {
+                        forced.eq_ignore_ascii_case("forced")
+                            && SettingType::Enum(values).check(name).is_ok()
+                    }
+                    _ => false,
+                };
+
+                if valid {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "expected one of: {}, optionally followed by 'forced'",
+                        values.join(", ")
+                    ))
+                }
+            }
+            SettingType::Cycles => {
+                let valid = !value.is_empty()
+                    && value.split_whitespace().all(|token| {
+                        let number = token.trim_right_matches('%');
+                        ["auto", "max", "fixed", "limit"]
+                            .iter()
+                            .any(|mode| mode.eq_ignore_ascii_case(token))
+                            || (!number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+                    });
+
+                if valid {
+                    Ok(())
+                } else {
+                    Err(String::from(
+                        "expected auto, max, fixed or a number of cycles, e.g. 'fixed 5000'",
+                    ))
+                }
+            }
+            SettingType::Text => Ok(()),
+        }
+    }
+}
+
+const BOOL: SettingType = SettingType::Bool;
+const TEXT: SettingType = SettingType::Text;
+
+const RATES: &[&str] = &[
+    "44100", "48000", "32000", "22050", "16000", "11025", "8000", "49716",
+];
+
+/// The sections and keys of a standard DOSBox 0.74 config, excluding
+/// `[autoexec]`.
+pub const SCHEMA: &[(&str, &[(&str, SettingType)])] = &[
+    (
+        "sdl",
+        &[
+            ("fullscreen", BOOL),
+            ("fulldouble", BOOL),
+            ("fullresolution", TEXT),
+            ("windowresolution", TEXT),
+            (
+                "output",
+                SettingType::Enum(&["surface", "overlay", "opengl", "openglnb", "ddraw"]),
+            ),
+            ("autolock", BOOL),
+            ("sensitivity", SettingType::Int { min: 1, max: 1000 }),
+            ("waitonerror", BOOL),
+            ("priority", TEXT),
+            ("mapperfile", TEXT),
+            ("usescancodes", BOOL),
+        ],
+    ),
+    (
+        "dosbox",
+        &[
+            ("language", TEXT),
+            (
+                "machine",
+                SettingType::Enum(&[
+                    "hercules",
+                    "cga",
+                    "tandy",
+                    "pcjr",
+                    "ega",
+                    "vgaonly",
+                    "svga_s3",
+                    "svga_et3000",
+                    "svga_et4000",
+                    "svga_paradise",
+                    "vesa_nolfb",
+                    "vesa_oldvbe",
+                ]),
+            ),
+            ("captures", TEXT),
+            ("memsize", SettingType::Int { min: 1, max: 63 }),
+        ],
+    ),
+    (
+        "render",
+        &[
+            ("frameskip", SettingType::Int { min: 0, max: 10 }),
+            ("aspect", BOOL),
+            (
+                "scaler",
+                SettingType::Scaler(&[
+                    "none",
+                    "normal2x",
+                    "normal3x",
+                    "advmame2x",
+                    "advmame3x",
+                    "advinterp2x",
+                    "advinterp3x",
+                    "hq2x",
+                    "hq3x",
+                    "2xsai",
+                    "super2xsai",
+                    "supereagle",
+                    "tv2x",
+                    "tv3x",
+                    "rgb2x",
+                    "rgb3x",
+                    "scan2x",
+                    "scan3x",
+                ]),
+            ),
+        ],
+    ),
+    (
+        "cpu",
+        &[
+            (
+                "core",
+                SettingType::Enum(&["auto", "dynamic", "normal", "simple"]),
+            ),
+            (
+                "cputype",
+                SettingType::Enum(&[
+                    "auto",
+                    "386",
+                    "386_slow",
+                    "486_slow",
+                    "pentium_slow",
+                    "386_prefetch",
+                ]),
+            ),
+            ("cycles", SettingType::Cycles),
+            (
+                "cycleup",
+                SettingType::Int {
+                    min: 1,
+                    max: 1_000_000,
+                },
+            ),
+            (
+                "cycledown",
+                SettingType::Int {
+                    min: 1,
+                    max: 1_000_000,
+                },
+            ),
+        ],
+    ),
+    (
+        "mixer",
+        &[
+            ("nosound", BOOL),
+            ("rate", SettingType::Enum(RATES)),
+            (
+                "blocksize",
+                SettingType::Enum(&["1024", "2048", "4096", "8192", "512", "256"]),
+            ),
+            ("prebuffer", SettingType::Int { min: 0, max: 1000 }),
+        ],
+    ),
+    (
+        "midi",
+        &[
+            (
+                "mpu401",
+                SettingType::Enum(&["intelligent", "uart", "none"]),
+            ),
+            (
+                "mididevice",
+                SettingType::Enum(&[
+                    "default",
+                    "win32",
+                    "alsa",
+                    "oss",
+                    "coreaudio",
+                    "coremidi",
+                    "none",
+                ]),
+            ),
+            ("midiconfig", TEXT),
+        ],
+    ),
+    (
+        "sblaster",
+        &[
+            (
+                "sbtype",
+                SettingType::Enum(&["sb1", "sb2", "sbpro1", "sbpro2", "sb16", "gb", "none"]),
+            ),
+            (
+                "sbbase",
+                SettingType::Enum(&["220", "240", "260", "280", "2a0", "2c0", "2e0", "300"]),
+            ),
+            (
+                "irq",
+                SettingType::Enum(&["7", "5", "3", "9", "10", "11", "12"]),
+            ),
+            ("dma", SettingType::Enum(&["1", "5", "0", "3", "6", "7"])),
+            ("hdma", SettingType::Enum(&["1", "5", "0", "3", "6", "7"])),
+            ("sbmixer", BOOL),
+            (
+                "oplmode",
+                SettingType::Enum(&["auto", "cms", "opl2", "dualopl2", "opl3", "none"]),
+            ),
+            ("oplemu", SettingType::Enum(&["default", "compat", "fast"])),
+            ("oplrate", SettingType::Enum(RATES)),
+        ],
+    ),
+    (
+        "gus",
+        &[
+            ("gus", BOOL),
+            ("gusrate", SettingType::Enum(RATES)),
+            (
+                "gusbase",
+                SettingType::Enum(&["240", "220", "260", "280", "2a0", "2c0", "2e0", "300"]),
+            ),
+            (
+                "gusirq",
+                SettingType::Enum(&["5", "3", "7", "9", "10", "11", "12"]),
+            ),
+            ("gusdma", SettingType::Enum(&["3", "0", "1", "5", "6", "7"])),
+            ("ultradir", TEXT),
+        ],
+    ),
+    (
+        "speaker",
+        &[
+            ("pcspeaker", BOOL),
+            (
+                "pcrate",
+                SettingType::Int {
+                    min: 8000,
+                    max: 96000,
+                },
+            ),
+            ("tandy", SettingType::Enum(&["auto", "on", "off"])),
+            ("tandyrate", SettingType::Enum(RATES)),
+            ("disney", BOOL),
+        ],
+    ),
+    (
+        "joystick",
+        &[
+            (
+                "joysticktype",
+                SettingType::Enum(&["auto", "2axis", "4axis", "4axis_2", "fcs", "ch", "none"]),
+            ),
+            ("timed", BOOL),
+            ("autofire", BOOL),
+            ("swap34", BOOL),
+            ("buttonwrap", BOOL),
+        ],
+    ),
+    (
+        "serial",
+        &[
+            ("serial1", TEXT),
+            ("serial2", TEXT),
+            ("serial3", TEXT),
+            ("serial4", TEXT),
+        ],
+    ),
+    (
+        "dos",
+        &[
+            ("xms", BOOL),
+            ("ems", BOOL),
+            ("umb", BOOL),
+            ("keyboardlayout", TEXT),
+        ],
+    ),
+    ("ipx", &[("ipx", BOOL)]),
+];
+
+/// Looks up the type of a setting, returning `None` for unknown sections or
+/// keys.
+pub fn setting_type(section: &str, key: &str) -> Option<SettingType> {
+    SCHEMA
+        .iter()
+        .find(|&&(name, _)| name.eq_ignore_ascii_case(section))
+        .and_then(|&(_, keys)| {
+            keys.iter()
+                .find(|&&(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|&(_, kind)| kind)
+        })
+}
+
+fn is_known_section(section: &str) -> bool {
+    section.eq_ignore_ascii_case("autoexec")
+        || SCHEMA
+            .iter()
+            .any(|&(name, _)| name.eq_ignore_ascii_case(section))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    UnknownSection,
+    UnknownKey,
+    InvalidValue(String),
+    Unsupported(Dialect),
+}
+
+/// A problem found while validating a config against the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub section: String,
+    pub key: String,
+    pub value: String,
+    pub kind: IssueKind,
+}
+
+impl Issue {
+    pub fn is_unknown(&self) -> bool {
+        self.kind == IssueKind::UnknownSection || self.kind == IssueKind::UnknownKey
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            IssueKind::UnknownSection => write!(f, "[{}] unknown section", self.section),
+            IssueKind::UnknownKey => write!(f, "[{}] {}: unknown key", self.section, self.key),
+            IssueKind::InvalidValue(ref reason) => write!(
+                f,
+                "[{}] {}={}: {}",
+                self.section, self.key, self.value, reason
+            ),
+            IssueKind::Unsupported(dialect) => write!(
+                f,
+                "[{}] {}={}: not supported by {}",
+                self.section, self.key, self.value, dialect
+            ),
+        }
+    }
+}
+
+impl DosboxConfig {
+    /// Checks every setting against the DOSBox 0.74 schema, returning the
+    /// issues sorted by section and key. Empty values are left to DOSBox's
+    /// defaults.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Vec::new();
+
+        for (section, settings) in &self.settings {
+            let known_section = is_known_section(section);
+
+            for (key, value) in settings {
+                let kind = match setting_type(section, key) {
+                    // Empty values fall back to DOSBox's defaults.
+                    Some(_) if value.trim().is_empty() => continue,
+                    Some(kind) => match kind.check(value) {
+                        Ok(()) => continue,
+                        Err(reason) => IssueKind::InvalidValue(reason),
+                    },
+                    None if known_section => IssueKind::UnknownKey,
+                    None => IssueKind::UnknownSection,
+                };
+
+                issues.push(Issue {
+                    section: section.clone(),
+                    key: key.clone(),
+                    value: value.clone(),
+                    kind: kind,
+                });
+            }
+        }
+
+        issues.sort_by(|a, b| (&a.section, &a.key).cmp(&(&b.section, &b.key)));
+        issues
+    }
+}
@@ -0,0 +1,567 @@
+//! A small expression language used by `{{...}}` template expansions.
+//!
+//! Supported syntax, from lowest to highest precedence:
+//!
+//! * `cond ? a : b` ternaries,
+//! * `||` and `&&`,
+//! * `==`, `!=`, `<`, `<=`, `>`, `>=`,
+//! * `+` and `-`,
+//! * `*`, `/` and `%`,
+//! * unary `-` and `!`,
+//! * numbers, `'single'` or `"double"` quoted strings, `true`, `false`,
+//!   parentheses, the variables `$i`, `$val` and `$count`, and bare mapping
+//!   keys such as `target`.
+//!
+//! `+` adds two numbers and concatenates anything else. The remaining
+//! arithmetic operators convert strings that hold a number and fail
+//! otherwise. Ordering comparisons are numeric when both sides are numbers,
+//! and compare strings otherwise. `$i` and `$count` are numbers, while `$val`
+//! and mapping values are strings.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExpressionError {
+    Parse { column: usize, message: String },
+    Evaluation { message: String },
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExpressionError::Parse {
+                column,
+                ref message,
+            } => write!(f, "column {}: {}", column, message),
+            ExpressionError::Evaluation { ref message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for ExpressionError {
+    fn description(&self) -> &str {
+        match *self {
+            ExpressionError::Parse { .. } => "failed parsing expression",
+            ExpressionError::Evaluation { .. } => "failed evaluating expression",
+        }
+    }
+}
+
+impl ExpressionError {
+    fn parse<T: Into<String>>(column: usize, message: T) -> ExpressionError {
+        ExpressionError::Parse {
+            column: column,
+            message: message.into(),
+        }
+    }
+
+    fn evaluation<T: Into<String>>(message: T) -> ExpressionError {
+        ExpressionError::Evaluation {
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn truthy(&self) -> bool {
+        match *self {
+            Value::Number(val) => val != 0.0,
+            Value::Str(ref val) => !val.is_empty(),
+            Value::Bool(val) => val,
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match *self {
+            Value::Number(val) => Some(val),
+            Value::Str(ref val) => val.trim().parse().ok(),
+            Value::Bool(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            // Only whole numbers an f64 holds exactly are printed as integers.
+            Value::Number(val) if val.fract() == 0.0 && val.abs() < 2f64.powi(53) => {
+                write!(f, "{}", val as i64)
+            }
+            Value::Number(val) => write!(f, "{}", val),
+            Value::Str(ref val) => write!(f, "{}", val),
+            Value::Bool(val) => write!(f, "{}", val),
+        }
+    }
+}
+
+/// Values available to an expression while it is evaluated.
+pub struct Scope<'a> {
+    pub i: usize,
+    pub val: &'a str,
+    pub count: usize,
+    pub mappings: &'a HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Not,
+    Question,
+    Colon,
+    LParen,
+    RParen,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    Ident(String),
+    Variable(String),
+    Op(Op),
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    Mapping(String),
+    Variable(String),
+    Unary(Op, Box<Node>),
+    Binary(Op, Box<Node>, Box<Node>),
+    Ternary(Box<Node>, Box<Node>, Box<Node>),
+}
+
+/// Splits an expression into tokens paired with their 1-based column.
+fn tokenise(input: &str) -> Result<Vec<(Token, usize)>, ExpressionError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let column = pos + 1;
+
+        if c.is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        if c.is_ascii_digit()
+            || (c == '.' && chars.get(pos + 1).map_or(false, char::is_ascii_digit))
+        {
+            let start = pos;
+            while pos < chars.len() && (chars[pos].is_ascii_digit() || chars[pos] == '.') {
+                pos += 1;
+            }
+            let text: String = chars[start..pos].iter().collect();
+            let number = text.parse().map_err(|_| {
+                ExpressionError::parse(column, format!("invalid number '{}'", text))
+            })?;
+            tokens.push((Token::Number(number), column));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = pos;
+            pos += 1;
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            let name: String = chars[start..pos].iter().collect();
+
+            let token = match name.as_str() {
+                "$i" | "$val" | "$count" => Token::Variable(name.clone()),
+                "$" => {
+                    return Err(ExpressionError::parse(
+                        column,
+                        "expected a variable name after '$'",
+                    ))
+                }
+                _ if c == '$' => {
+                    return Err(ExpressionError::parse(
+                        column,
+                        format!("unknown variable '{}', expected $i, $val or $count", name),
+                    ))
+                }
+                "true" => Token::Bool(true),
+                "false" => Token::Bool(false),
+                _ => Token::Ident(name.clone()),
+            };
+
+            tokens.push((token, column));
+            continue;
+        }
+
+        if c == '\'' || c == '"' {
+            let mut val = String::new();
+            pos += 1;
+            loop {
+                match chars.get(pos) {
+                    None => return Err(ExpressionError::parse(column, "unterminated string")),
+                    Some(&'\\') => {
+                        match chars.get(pos + 1) {
+                            Some(&escaped) => val.push(escaped),
+                            None => {
+                                return Err(ExpressionError::parse(column, "unterminated string"))
+                            }
+                        }
+                        pos += 2;
+                    }
+                    Some(&quote) if quote == c => {
+                        pos += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        val.push(other);
+                        pos += 1;
+                    }
+                }
+            }
+            tokens.push((Token::Str(val), column));
+            continue;
+        }
+
+        let next = chars.get(pos + 1).cloned();
+        let (op, len) = match (c, next) {
+            ('=', Some('=')) => (Op::Eq, 2),
+            ('!', Some('=')) => (Op::Ne, 2),
+            ('<', Some('=')) => (Op::Le, 2),
+            ('>', Some('=')) => (Op::Ge, 2),
+            ('&', Some('&')) => (Op::And, 2),
+            ('|', Some('|')) => (Op::Or, 2),
+            ('<', _) => (Op::Lt, 1),
+            ('>', _) => (Op::Gt, 1),
+            ('!', _) => (Op::Not, 1),
+            ('+', _) => (Op::Add, 1),
+            ('-', _) => (Op::Sub, 1),
+            ('*', _) => (Op::Mul, 1),
+            ('/', _) => (Op::Div, 1),
+            ('%', _) => (Op::Rem, 1),
+            ('?', _) => (Op::Question, 1),
+            (':', _) => (Op::Colon, 1),
+            ('(', _) => (Op::LParen, 1),
+            (')', _) => (Op::RParen, 1),
+            _ => {
+                return Err(ExpressionError::parse(
+                    column,
+                    format!("unexpected character '{}'", c),
+                ))
+            }
+        };
+
+        tokens.push((Token::Op(op), column));
+        pos += len;
+    }
+
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, op: Op) -> bool {
+        if *self.peek() == Token::Op(op) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, op: Op, what: &str) -> Result<(), ExpressionError> {
+        if self.eat(op) {
+            return Ok(());
+        }
+        Err(ExpressionError::parse(
+            self.column(),
+            format!("expected {}", what),
+        ))
+    }
+
+    fn enter(&mut self) -> Result<(), ExpressionError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(ExpressionError::parse(
+                self.column(),
+                "expression is nested too deeply",
+            ));
+        }
+        Ok(())
+    }
+
+    fn ternary(&mut self) -> Result<Node, ExpressionError> {
+        self.enter()?;
+        let condition = self.binary(0)?;
+
+        let node = if self.eat(Op::Question) {
+            let then = self.ternary()?;
+            self.expect(Op::Colon, "':' in ternary expression")?;
+            let otherwise = self.ternary()?;
+            Node::Ternary(Box::new(condition), Box::new(then), Box::new(otherwise))
+        } else {
+            condition
+        };
+
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, ExpressionError> {
+        const LEVELS: [&[Op]; 5] = [
+            &[Op::Or],
+            &[Op::And],
+            &[Op::Eq, Op::Ne, Op::Lt, Op::Le, Op::Gt, Op::Ge],
+            &[Op::Add, Op::Sub],
+            &[Op::Mul, Op::Div, Op::Rem],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+
+        loop {
+            let op = match *self.peek() {
+                Token::Op(op) if LEVELS[level].contains(&op) => op,
+                _ => break,
+            };
+            self.advance();
+            let rhs = self.binary(level + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        match *self.peek() {
+            Token::Op(op @ Op::Sub) | Token::Op(op @ Op::Not) => {
+                self.enter()?;
+                self.advance();
+                let operand = self.unary()?;
+                self.depth -= 1;
+                Ok(Node::Unary(op, Box::new(operand)))
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, ExpressionError> {
+        let column = self.column();
+
+        match self.advance() {
+            Token::Number(val) => Ok(Node::Literal(Value::Number(val))),
+            Token::Str(val) => Ok(Node::Literal(Value::Str(val))),
+            Token::Bool(val) => Ok(Node::Literal(Value::Bool(val))),
+            Token::Ident(name) => Ok(Node::Mapping(name)),
+            Token::Variable(name) => Ok(Node::Variable(name)),
+            Token::Op(Op::LParen) => {
+                let node = self.ternary()?;
+                self.expect(Op::RParen, "')'")?;
+                Ok(node)
+            }
+            Token::End => Err(ExpressionError::parse(
+                column,
+                "unexpected end of expression",
+            )),
+            Token::Op(_) => Err(ExpressionError::parse(column, "expected a value")),
+        }
+    }
+}
+
+/// A parsed expression that can be evaluated repeatedly.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(input: &str) -> Result<Expression, ExpressionError> {
+        let mut parser = Parser {
+            tokens: tokenise(input)?,
+            pos: 0,
+            depth: 0,
+        };
+
+        let root = parser.ternary()?;
+
+        if *parser.peek() != Token::End {
+            return Err(ExpressionError::parse(
+                parser.column(),
+                "unexpected token after expression",
+            ));
+        }
+
+        Ok(Expression { root: root })
+    }
+
+    /// Returns the mapping keys referenced by the expression.
+    pub fn mappings(&self) -> Vec<&str> {
+        let mut keys = Vec::new();
+        Expression::collect_mappings(&self.root, &mut keys);
+        keys
+    }
+
+    fn collect_mappings<'a>(node: &'a Node, keys: &mut Vec<&'a str>) {
+        match *node {
+            Node::Mapping(ref key) => keys.push(key),
+            Node::Unary(_, ref operand) => Expression::collect_mappings(operand, keys),
+            Node::Binary(_, ref lhs, ref rhs) => {
+                Expression::collect_mappings(lhs, keys);
+                Expression::collect_mappings(rhs, keys);
+            }
+            Node::Ternary(ref condition, ref then, ref otherwise) => {
+                Expression::collect_mappings(condition, keys);
+                Expression::collect_mappings(then, keys);
+                Expression::collect_mappings(otherwise, keys);
+            }
+            Node::Literal(_) | Node::Variable(_) => {}
+        }
+    }
+
+    pub fn evaluate(&self, scope: &Scope) -> Result<Value, ExpressionError> {
+        Expression::evaluate_node(&self.root, scope)
+    }
+
+    fn evaluate_node(node: &Node, scope: &Scope) -> Result<Value, ExpressionError> {
+        match *node {
+            Node::Literal(ref val) => Ok(val.clone()),
+            Node::Mapping(ref key) => scope
+                .mappings
+                .get(key)
+                .map(|val| Value::Str(val.clone()))
+                .ok_or_else(|| ExpressionError::evaluation(format!("undefined mapping '{}'", key))),
+            Node::Variable(ref name) => match name.as_str() {
+                "$i" => Ok(Value::Number(scope.i as f64)),
+                "$count" => Ok(Value::Number(scope.count as f64)),
+                _ => Ok(Value::Str(String::from(scope.val))),
+            },
+            Node::Unary(op, ref operand) => {
+                let operand = Expression::evaluate_node(operand, scope)?;
+                match op {
+                    Op::Not => Ok(Value::Bool(!operand.truthy())),
+                    _ => Ok(Value::Number(-Expression::number(&operand, "-")?)),
+                }
+            }
+            Node::Ternary(ref condition, ref then, ref otherwise) => {
+                if Expression::evaluate_node(condition, scope)?.truthy() {
+                    Expression::evaluate_node(then, scope)
+                } else {
+                    Expression::evaluate_node(otherwise, scope)
+                }
+            }
+            Node::Binary(Op::And, ref lhs, ref rhs) => Ok(Value::Bool(
+                Expression::evaluate_node(lhs, scope)?.truthy()
+                    && Expression::evaluate_node(rhs, scope)?.truthy(),
+            )),
+            Node::Binary(Op::Or, ref lhs, ref rhs) => Ok(Value::Bool(
+                Expression::evaluate_node(lhs, scope)?.truthy()
+                    || Expression::evaluate_node(rhs, scope)?.truthy(),
+            )),
+            Node::Binary(op, ref lhs, ref rhs) => {
+                let lhs = Expression::evaluate_node(lhs, scope)?;
+                let rhs = Expression::evaluate_node(rhs, scope)?;
+                Expression::binary(op, &lhs, &rhs)
+            }
+        }
+    }
+
+    fn number(val: &Value, op: &str) -> Result<f64, ExpressionError> {
+        val.number().ok_or_else(|| {
+            ExpressionError::evaluation(format!("'{}' is not a number, required by '{}'", val, op))
+        })
+    }
+
+    fn binary(op: Op, lhs: &Value, rhs: &Value) -> Result<Value, ExpressionError> {
+        match op {
+            Op::Add => match (lhs, rhs) {
+                (&Value::Number(a), &Value::Number(b)) => Ok(Value::Number(a + b)),
+                _ => Ok(Value::Str(format!("{}{}", lhs, rhs))),
+            },
+            Op::Sub => Ok(Value::Number(
+                Expression::number(lhs, "-")? - Expression::number(rhs, "-")?,
+            )),
+            Op::Mul => Ok(Value::Number(
+                Expression::number(lhs, "*")? * Expression::number(rhs, "*")?,
+            )),
+            Op::Div | Op::Rem => {
+                let symbol = if op == Op::Div { "/" } else { "%" };
+                let a = Expression::number(lhs, symbol)?;
+                let b = Expression::number(rhs, symbol)?;
+                if b == 0.0 {
+                    return Err(ExpressionError::evaluation("division by zero"));
+                }
+                Ok(Value::Number(if op == Op::Div { a / b } else { a % b }))
+            }
+            Op::Eq | Op::Ne => {
+                let equal = match (lhs, rhs) {
+                    (&Value::Number(a), &Value::Number(b)) => a == b,
+                    _ => lhs.to_string() == rhs.to_string(),
+                };
+                Ok(Value::Bool(equal == (op == Op::Eq)))
+            }
+            _ => {
+                let ordering = match (lhs, rhs) {
+                    (&Value::Number(a), &Value::Number(b)) => a.partial_cmp(&b),
+                    _ => Some(lhs.to_string().cmp(&rhs.to_string())),
+                };
+
+                let ordering = ordering
+                    .ok_or_else(|| ExpressionError::evaluation("values cannot be compared"))?;
+
+                Ok(Value::Bool(match op {
+                    Op::Lt => ordering == ::std::cmp::Ordering::Less,
+                    Op::Le => ordering != ::std::cmp::Ordering::Greater,
+                    Op::Gt => ordering == ::std::cmp::Ordering::Greater,
+                    _ => ordering != ::std::cmp::Ordering::Less,
+                }))
+            }
+        }
+    }
+}
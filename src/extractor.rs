@@ -2,15 +2,21 @@ use std::path::Path;
 use std::error::Error;
 
 use command::Command;
+use config::Environment;
 use mappings::Mappings;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+#[schemars(deny_unknown_fields)]
 pub struct Extractor {
     extensions: Vec<String>,
     command: Command,
 }
 
 impl Extractor {
+    pub fn command(&self) -> &Command {
+        &self.command
+    }
+
     pub fn can_extract<T>(&self, path: &T) -> bool
     where
         T: AsRef<str>,
@@ -23,23 +29,45 @@ impl Extractor {
         self.extensions.contains(&extension.into_owned())
     }
 
-    pub fn extract<T>(&self, archive: &T, target: &T) -> Result<(), Box<Error>>
+    /// Resolves the extraction command for a single archive, which overrides
+    /// `{archive}` and `{archive_stem}` in the given standard mappings.
+    pub fn resolve<T>(
+        &self,
+        archive: &T,
+        mappings: &Mappings,
+        environment: &Environment,
+    ) -> Result<Command, Box<Error>>
     where
         T: AsRef<str>,
     {
-        let mut mappings = Mappings::new();
+        let mut mappings = mappings.clone();
         mappings.insert("archive", archive);
-        mappings.insert("target", target);
-
-        println!("Mappings: {:#?}", mappings);
+        mappings.insert(
+            "archive_stem",
+            &Path::new(archive.as_ref())
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy(),
+        );
 
         let mut command = self.command.clone();
-        command.apply_mappings(&mappings);
+        command.apply_environment(environment)?;
+        command.apply_mappings(&mappings)?;
 
         println!("Command: {:#?}", command);
 
-        command.execute()?;
+        Ok(command)
+    }
 
-        Ok(())
+    pub fn extract<T>(
+        &self,
+        archive: &T,
+        mappings: &Mappings,
+        environment: &Environment,
+    ) -> Result<(), Box<Error>>
+    where
+        T: AsRef<str>,
+    {
+        self.resolve(archive, mappings, environment)?.execute()
     }
 }
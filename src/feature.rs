@@ -2,11 +2,18 @@ extern crate regex;
 use regex::Regex;
 
 use deserialisers;
+use schemas;
+use serialisers;
+use utils;
 
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct Feature {
-    #[serde(deserialize_with = "deserialisers::regex")] pattern: Regex,
+    #[serde(deserialize_with = "deserialisers::regex")]
+    #[serde(serialize_with = "serialisers::regex")]
+    #[schemars(schema_with = "schemas::regex")]
+    pattern: Regex,
 
     #[serde(default = "Feature::default_weight")] weight: usize,
 }
@@ -27,4 +34,13 @@ impl Feature {
     pub fn score_all(&self, items: &[&str]) -> usize {
         items.iter().fold(0, |sum, &item| sum + self.score(item))
     }
+
+    /// Returns the named capture groups of the first matching item.
+    pub fn captures(&self, items: &[&str]) -> Vec<(String, String)> {
+        items
+            .iter()
+            .find(|item| self.pattern.is_match(item))
+            .map(|item| utils::named_captures(::std::slice::from_ref(&self.pattern), item))
+            .unwrap_or_default()
+    }
 }
@@ -2,53 +2,61 @@
 #![cfg_attr(feature = "clippy", plugin(clippy))]
 #![feature(plugin)]
 
-extern crate failure;
-
 #[macro_use]
 extern crate lazy_static;
 
 #[macro_use]
 extern crate serde_derive;
 
-#[macro_use]
-extern crate failure_derive;
-
 extern crate clap;
-extern crate eval;
 extern crate regex;
+#[macro_use]
+extern crate schemars;
 extern crate serde;
 extern crate serde_json;
+extern crate toml;
 
 use std::error::Error;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 
-mod command;
-mod config;
+pub mod command;
+pub mod config;
 mod deserialisers;
 pub mod dosbox_config;
+pub mod expression;
 mod extractor;
 mod feature;
 pub mod filters;
-mod mappings;
+pub mod mappings;
 mod menu;
 mod profile;
+mod schemas;
+mod serialisers;
 pub mod template;
 mod utils;
 
-use config::Configuration;
+use config::{ConfigCommand, Configuration};
 
 fn extract(config: &Configuration) -> Result<(), Box<Error>> {
     let target_path = Path::new(&config.target_dir);
-    if !target_path.exists() {
-        fs::create_dir_all(target_path)?;
+    if !target_path.exists() || config.dry_run {
+        if !config.dry_run {
+            fs::create_dir_all(target_path)?;
+        }
 
         if let Some(extractor) = config.get_extractor() {
             println!("Extractor: {:#?}", extractor);
 
+            let mappings = config.mappings(None);
+
             for archive in &config.archives {
-                extractor.extract(archive, &config.target_dir)?;
+                if config.dry_run {
+                    extractor.resolve(archive, &mappings, &config.environment)?;
+                } else {
+                    extractor.extract(archive, &mappings, &config.environment)?;
+                }
             }
         } else {
             //return Err(Box::new(Error()));
@@ -58,44 +66,89 @@ fn extract(config: &Configuration) -> Result<(), Box<Error>> {
     Ok(())
 }
 
-fn determine_executor(config: &Configuration) -> Result<&profile::Profile, Box<Error>> {
-    let files = utils::recursive_find_all(&config.target_dir)?;
-
-    let file_paths: Vec<&str> = files
-        .iter()
-        .map(|file| file.as_path().to_str().unwrap())
-        .collect();
-
+fn determine_executor<'a>(
+    config: &'a Configuration,
+    file_paths: &[&str],
+) -> Result<(&'a str, &'a profile::Profile), Box<Error>> {
     let (profile, score) = config
         .profiles
         .iter()
-        .map(|(key, profile)| (key, profile.feature_score(&file_paths)))
+        .map(|(key, profile)| (key, profile.feature_score(file_paths)))
         .max_by_key(|&(_, score)| score)
-        .unwrap();
+        .ok_or("no profiles configured")?;
 
     println!("Determined the following profile: {} ({})", profile, score);
 
-    Ok(&config.profiles[profile])
+    Ok((profile, &config.profiles[profile]))
 }
 
 fn execute(config: &Configuration) -> Result<(), Box<Error>> {
-    let executor = config.get_profile().unwrap_or(determine_executor(config)?);
+    let files = utils::recursive_find_all(&config.target_dir)?;
+
+    let file_paths: Vec<&str> = files
+        .iter()
+        .map(|file| file.as_path().to_str().unwrap())
+        .collect();
+
+    if config.dry_run {
+        println!("Standard mappings:\n{}", config.mappings(None));
+    }
+
+    let (name, executor) = match config.get_profile() {
+        Some(profile) => profile,
+        None if config.dry_run && file_paths.is_empty() => {
+            println!(
+                "No profile could be determined as a dry run doesn't extract the archives, \
+                 use --profile to choose one."
+            );
+            return Ok(());
+        }
+        None => determine_executor(config, &file_paths)?,
+    };
+    let executor = &config
+        .sidecar
+        .apply(executor)
+        .map_err(|err| format!("profile '{}': {}", name, err))?;
 
     let target_dir = PathBuf::from(&config.target_dir);
+    let mut mappings = config.mappings(Some(name));
+    executor.insert_feature_captures(&file_paths, &mut mappings);
+
+    let candidates = match config.sidecar.executable {
+        Some(ref executable) => vec![executor.candidate(&target_dir, executable)?],
+        None => executor.candidates(&target_dir)?,
+    };
+    let executables: Vec<PathBuf> = candidates
+        .iter()
+        .map(|candidate| candidate.path.clone())
+        .collect();
 
-    let mut executables =
-        utils::recursive_find(&config.target_dir, executor.executables.as_slice())?;
-    executables.sort();
-    utils::strip_prefix(&mut executables, &target_dir)?;
+    if config.dry_run {
+        println!("Executables: {:#?}", executables);
+        println!("Mappings:\n{}", mappings);
 
-    if executables.len() > 1 {
+        let selected = &candidates[..candidates.len().min(1)];
+
+        if let Some((path, dosbox_config)) =
+            executor.dosbox_config(selected, &mappings, &config.environment)?
+        {
+            println!("DOSBox config ({}): {:#?}", path.display(), dosbox_config);
+        }
+
+        let command = executor.resolve(selected, &mappings, &config.environment)?;
+        println!("Command: {:#?}", command);
+    } else if candidates.len() > 1 {
         let mut menu = menu::Menu::from(&executables);
         menu.display();
-        let selected: Vec<&usize> = menu.get_selected().iter().collect();
-        let executable = &executables[*selected[0]];
-        executor.run(executable, &target_dir)?;
-    } else if executables.len() == 1 {
-        executor.run(&PathBuf::from(&executables[0]), &target_dir)?;
+        let mut selected: Vec<&usize> = menu.get_selected().iter().collect();
+        selected.sort();
+        let selected: Vec<profile::Candidate> = selected
+            .iter()
+            .map(|&&index| candidates[index].clone())
+            .collect();
+        executor.run(&selected, &mappings, &config.environment)?;
+    } else if candidates.len() == 1 {
+        executor.run(&candidates, &mappings, &config.environment)?;
     } else {
         println!("Could not find any suitable executables.");
     }
@@ -104,8 +157,18 @@ fn execute(config: &Configuration) -> Result<(), Box<Error>> {
 }
 
 pub fn run() -> Result<(), Box<Error>> {
+    if let Some(command) = ConfigCommand::current() {
+        return command.run();
+    }
+
     let config = Configuration::load()?;
+    if config.archives.is_empty() {
+        return Err(From::from("no archives were given, as arguments or in RXR_ARCHIVES"));
+    }
+
     extract(&config)?;
-    execute(&config)?;
+
+    // The sidecar file of the extracted root is only found once extracted.
+    execute(&config.with_sidecars()?)?;
     Ok(())
 }
@@ -1,17 +1,61 @@
 use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 
-#[derive(Serialize, Deserialize, Debug)]
+use regex::Regex;
+
+use template::Template;
+
+/// The mappings every command gets, see `Configuration::mappings`.
+pub const STANDARD: &[&str] = &[
+    "target",
+    "data_dir",
+    "temp_dir",
+    "config_dir",
+    "save_dir",
+    "profile",
+    "archives",
+    "archive",
+    "archive_stem",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Mappings {
     mappings: HashMap<String, String>,
+
+    #[serde(default)]
+    items: Vec<String>,
 }
 
 impl Mappings {
     pub fn new() -> Mappings {
         Mappings {
             mappings: HashMap::new(),
+            items: Vec::new(),
         }
     }
 
+    /// Whether a key is one of the standard mappings, including the numbered
+    /// `{archive_N}`, `{archive_stem_N}` and `{save_dir_N}`.
+    pub fn is_standard(key: &str) -> bool {
+        lazy_static! {
+            static ref NUMBERED: Regex = Regex::new(r"^(archive|archive_stem|save_dir)_\d+$").unwrap();
+        }
+
+        STANDARD.contains(&key) || NUMBERED.is_match(key)
+    }
+
+    /// Sets the items that `{{...}}` expansions iterate over as `$val`.
+    pub fn set_items<T>(&mut self, items: &[T])
+    where
+        T: AsRef<str>,
+    {
+        self.items = items
+            .iter()
+            .map(|item| String::from(item.as_ref()))
+            .collect();
+    }
+
     pub fn insert<K: ?Sized, V: ?Sized>(&mut self, key: &K, val: &V) -> &Mappings
     where
         K: AsRef<str>,
@@ -24,38 +68,60 @@ impl Mappings {
         self
     }
 
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.mappings.get(key).map(String::as_str)
+    }
+
+    /// A mapping is considered set when it exists and is not empty.
+    pub fn is_set(&self, key: &str) -> bool {
+        self.mappings.get(key).map_or(false, |val| !val.is_empty())
+    }
+
     pub fn replace_all<T: ?Sized>(&self, val: &T) -> String
     where
         T: AsRef<str>,
     {
         let mut result = String::from(val.as_ref()).replace("\"", "");
-
-        for (key, val) in &self.mappings {
-            result = result.replace(&format!("{{{}}}", key), val);
-        }
-
+        self.replace(&mut result);
         result
     }
 
     pub fn replace(&self, val: &mut String) -> () {
-        for (mkey, mval) in &self.mappings {
-            *val = val.replace(&format!("{{{}}}", mkey), mval);
+        let substituted = Template::new(val).substitute(&self.mappings);
+
+        if let Ok(substituted) = substituted {
+            *val = substituted;
         }
     }
 
-    pub fn replace_vec(&self, values: &mut Vec<String>) -> () {
-        for val in values.iter_mut() {
-            for (mkey, mval) in &self.mappings {
-                *val = val.replace(&format!("{{{}}}", mkey), mval);
-            }
+    /// Substitutes a value, producing one value per item when it contains
+    /// expansions.
+    pub fn expand(&self, val: &str) -> Result<Vec<String>, Box<Error>> {
+        let template = Template::new(val);
+
+        if template.has_expansions() {
+            return Ok(template.expand(&self.items, &self.mappings)?);
         }
+
+        Ok(vec![template.substitute(&self.mappings)?])
     }
 
     pub fn replace_map(&self, values: &mut HashMap<String, String>) -> () {
         for val in values.values_mut() {
-            for (mkey, mval) in &self.mappings {
-                *val = val.replace(&format!("{{{}}}", mkey), mval);
-            }
+            self.replace(val);
         }
     }
 }
+
+impl fmt::Display for Mappings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut keys: Vec<&String> = self.mappings.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            writeln!(f, "  {{{}}} = {}", key, self.mappings[key])?;
+        }
+
+        Ok(())
+    }
+}
@@ -3,38 +3,175 @@ extern crate serde_json;
 extern crate regex;
 use regex::Regex;
 
+use std::cmp::Ordering;
 use std::error::Error;
+use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
-use std::ffi::OsStr;
 
 use command::Command;
+use config::Environment;
+use dosbox_config::{gog_base, AutoexecMode, Dialect, DosboxConfig, GogRole, MergeOptions};
 use feature::Feature;
 use mappings::Mappings;
 
 use deserialisers;
+use schemas;
+use serialisers;
+use utils;
 
-#[derive(Deserialize, Debug)]
+/// An executable found in the target directory, relative to it, along with
+/// the named capture groups of the executable pattern that matched it.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub captures: Vec<(String, String)>,
+}
+
+impl Candidate {
+    /// Orders candidates by the values of the capture groups they share, in
+    /// the order of this candidate's groups, and then by path, comparing
+    /// numbers by value.
+    fn cmp_natural(&self, other: &Candidate) -> Ordering {
+        self.captures
+            .iter()
+            .filter_map(|&(ref name, ref a)| {
+                other
+                    .captures
+                    .iter()
+                    .find(|&&(ref other_name, _)| other_name == name)
+                    .map(|&(_, ref b)| utils::natural_cmp(a, b))
+            })
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| {
+                utils::natural_cmp(&self.path.to_string_lossy(), &other.path.to_string_lossy())
+            })
+    }
+}
+
+/// Whether a capture group is named after a mapping rxr sets itself.
+fn is_reserved(key: &str) -> bool {
+    Mappings::is_standard(key) || ["executable", "executable_dir", "dosbox_conf"].contains(&key)
+}
+
+/// Generates a DOSBox config for the selected executable, layered as the base
+/// config, the generated launcher or GOG configs, the inline config and the
+/// per-game overrides. Missing base and override files are skipped.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(deny_unknown_fields)]
+pub struct DosboxOptions {
+    #[serde(default)]
+    #[schemars(schema_with = "schemas::optional_template")]
+    pub base: Option<String>,
+
+    /// Treats the selected executable as the game or setup config of a GOG
+    /// release, layered over the base config next to it, instead of
+    /// generating a launcher.
+    #[serde(default)]
+    pub gog: bool,
+
+    /// Directory relative mounts of GOG configs are resolved against.
+    /// Defaults to the directory of the selected config.
+    #[serde(default)]
+    #[schemars(schema_with = "schemas::optional_template")]
+    pub mount_base: Option<String>,
+
+    /// Dialect the generated config is written for. Every layer is
+    /// translated into it from the dialect it is detected as, so that GOG
+    /// configs for 0.74 can be layered under a Staging or DOSBox-X base.
+    #[serde(default)]
+    pub dialect: Option<Dialect>,
+
+    /// Settings and autoexec commands given in the profile itself.
+    #[serde(default)]
+    pub config: Option<DosboxConfig>,
+
+    #[serde(default)]
+    #[schemars(schema_with = "schemas::optional_template")]
+    pub overrides: Option<String>,
+
+    /// Name of the generated config in the target directory.
+    #[serde(default = "DosboxOptions::default_output")]
+    pub output: String,
+}
+
+impl DosboxOptions {
+    fn default_output() -> String {
+        String::from("rxr-dosbox.conf")
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[schemars(deny_unknown_fields)]
 pub struct Profile {
     pub command: Command,
 
-    #[serde(default = "Vec::new")]
+    #[serde(default)]
     #[serde(deserialize_with = "deserialisers::regex_array")]
+    #[serde(serialize_with = "serialisers::regex_array")]
+    #[schemars(schema_with = "schemas::regex_array")]
     pub executables: Vec<Regex>,
 
-    #[serde(default = "Vec::new")]
+    #[serde(default)]
     pub features: Vec<Feature>,
+
+    #[serde(default)]
+    pub dosbox: Option<DosboxOptions>,
 }
 
 impl Profile {
-    pub fn run<T>(&self, executable: &T, target: &T) -> Result<(), Box<Error>>
-    where
-        T: AsRef<OsStr>,
-    {
-        let executable = executable.as_ref().to_string_lossy().into_owned();
-        let target = target.as_ref().to_string_lossy().into_owned();
+    /// Finds the executables in the target directory, sorted naturally by
+    /// their captured values and paths.
+    pub fn candidates(&self, target_dir: &PathBuf) -> Result<Vec<Candidate>, Box<Error>> {
+        let mut candidates = Vec::new();
+
+        for path in utils::recursive_find(target_dir, self.executables.as_slice())? {
+            candidates.push(Candidate {
+                captures: utils::named_captures(&self.executables, &path.to_string_lossy()),
+                path: PathBuf::from(path.strip_prefix(target_dir)?),
+            });
+        }
+
+        candidates.sort_by(Candidate::cmp_natural);
+
+        if self.dosbox.as_ref().map_or(false, |dosbox| dosbox.gog) {
+            candidates.sort_by_key(|c| GogRole::of(&c.path) == Some(GogRole::Setup));
+        }
+
+        Ok(candidates)
+    }
+
+    /// Adds the named capture groups of every feature to the mappings. Groups
+    /// named after a standard or executable mapping are skipped, so they
+    /// can't replace `{target}` and the like.
+    pub fn insert_feature_captures(&self, items: &[&str], mappings: &mut Mappings) {
+        for feature in &self.features {
+            for (key, val) in feature.captures(items) {
+                if !is_reserved(&key) {
+                    mappings.insert(&key, &val);
+                }
+            }
+        }
+    }
+
+    /// Adds the mappings of the selected executables. The first one provides
+    /// `{executable}`, `{executable_dir}` and its named captures, while all of
+    /// them are available to `{{...}}` expansions. Profiles generating a
+    /// DOSBox config also get `{dosbox_conf}`.
+    fn executable_mappings(&self, candidates: &[Candidate], mappings: &Mappings) -> Mappings {
+        let executables: Vec<String> = candidates
+            .iter()
+            .map(|candidate| candidate.path.to_string_lossy().into_owned())
+            .collect();
+        let executable = executables.first().cloned().unwrap_or_default();
+
+        let mut mappings = mappings.clone();
+        mappings.set_items(&executables);
 
-        let mut mappings = Mappings::new();
+        let captures = candidates.iter().take(1).flat_map(|c| &c.captures);
+        for &(ref key, ref val) in captures.filter(|&&(ref key, _)| !is_reserved(key)) {
+            mappings.insert(key, val);
+        }
 
         mappings.insert("executable", &executable);
         mappings.insert(
@@ -44,13 +181,165 @@ impl Profile {
                 .unwrap_or_else(|| Path::new("."))
                 .to_string_lossy(),
         );
-        mappings.insert("target", &target);
+
+        if let Some(ref dosbox) = self.dosbox {
+            let path = Path::new(mappings.get("target").unwrap_or(".")).join(&dosbox.output);
+            mappings.insert("dosbox_conf", &path.to_string_lossy());
+        }
+
+        mappings
+    }
+
+    /// Resolves the profile's command for the selected executables, on top of
+    /// the given standard mappings.
+    pub fn resolve(
+        &self,
+        candidates: &[Candidate],
+        mappings: &Mappings,
+        environment: &Environment,
+    ) -> Result<Command, Box<Error>> {
+        let mappings = self.executable_mappings(candidates, mappings);
 
         let mut command = self.command.clone();
-        command.apply_mappings(&mappings);
-        command.execute()?;
+        command.apply_environment(environment)?;
+        command.apply_mappings(&mappings)?;
+
+        Ok(command)
+    }
+
+    /// Generates the DOSBox config for the selected executables, along with
+    /// the path it belongs at, when the profile asks for one.
+    pub fn dosbox_config(
+        &self,
+        candidates: &[Candidate],
+        mappings: &Mappings,
+        environment: &Environment,
+    ) -> Result<Option<(PathBuf, DosboxConfig)>, Box<Error>> {
+        let dosbox = match self.dosbox {
+            Some(ref dosbox) => dosbox,
+            None => return Ok(None),
+        };
+
+        // GOG configs can't be layered without a selected config.
+        if dosbox.gog && candidates.is_empty() {
+            return Ok(None);
+        }
+
+        let mappings = self.executable_mappings(candidates, mappings);
+        let resolve = |template: &str| -> Result<PathBuf, Box<Error>> {
+            Ok(PathBuf::from(mappings.replace_all(&environment.resolve(template)?)))
+        };
+
+        let translate = |config: DosboxConfig, layer: &str| match dosbox.dialect {
+            Some(dialect) => {
+                let (translated, issues) = config.translate(Dialect::detect(&config), dialect);
+
+                for issue in issues {
+                    eprintln!("[rxr warning] {}: {}", layer, issue);
+                }
+
+                translated
+            }
+            None => config,
+        };
+
+        let target = PathBuf::from(mappings.get("target").unwrap_or("."));
+        let executable = PathBuf::from(mappings.get("executable").unwrap_or_default());
+        let mut config = if dosbox.gog {
+            let overlay = target.join(&executable);
+            let mount_base = match dosbox.mount_base {
+                Some(ref mount_base) => resolve(mount_base)?,
+                None => PathBuf::from(overlay.parent().unwrap_or(&target)),
+            };
+
+            translate(Profile::gog_config(&overlay, &mount_base)?, &overlay.to_string_lossy())
+        } else {
+            DosboxConfig::launcher(&target, &executable)
+        };
+
+        if let Some(ref base) = dosbox.base {
+            let base = resolve(base)?;
+            if base.exists() {
+                let layer = translate(DosboxConfig::read(&base)?, &base.to_string_lossy());
+                config = layer.merge(&config);
+            }
+        }
+
+        let replace_autoexec = MergeOptions {
+            autoexec: AutoexecMode::Replace,
+            ..Default::default()
+        };
+
+        if let Some(ref inline) = dosbox.config {
+            let layer = translate(inline.clone(), "inline config");
+            config = config.merge_with(&layer, &replace_autoexec);
+        }
+
+        if let Some(ref overrides) = dosbox.overrides {
+            let overrides = resolve(overrides)?;
+            if overrides.exists() {
+                let layer = translate(
+                    DosboxConfig::read(&overrides)?,
+                    &overrides.to_string_lossy(),
+                );
+                config = config.merge_with(&layer, &replace_autoexec);
+            }
+        }
+
+        Ok(Some((
+            PathBuf::from(mappings.get("dosbox_conf").unwrap_or_default()),
+            config,
+        )))
+    }
+
+    /// Layers a GOG game or setup config over its base config, resolving
+    /// relative mounts against `mount_base`.
+    fn gog_config(overlay: &Path, mount_base: &Path) -> Result<DosboxConfig, Box<Error>> {
+        let files: Vec<PathBuf> = fs::read_dir(overlay.parent().unwrap_or_else(|| Path::new(".")))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .collect();
+
+        let overlay_config = DosboxConfig::read(&overlay.to_path_buf())?;
+        let mut config = match gog_base(overlay, &files) {
+            Some(base) => DosboxConfig::read(base)?.merge(&overlay_config),
+            None => overlay_config,
+        };
+
+        config.rebase_mounts(mount_base);
+        Ok(config)
+    }
+
+    pub fn run(
+        &self,
+        candidates: &[Candidate],
+        mappings: &Mappings,
+        environment: &Environment,
+    ) -> Result<(), Box<Error>> {
+        if let Some((path, config)) = self.dosbox_config(candidates, mappings, environment)? {
+            config.write(&path)?;
+        }
+
+        self.resolve(candidates, mappings, environment)?.execute()
+    }
+
+    /// Returns the given executable, relative to the target directory, as a
+    /// candidate even if none of the executable patterns match it.
+    pub fn candidate(&self, target_dir: &PathBuf, executable: &str) -> Result<Candidate, Box<Error>> {
+        let path = target_dir.join(executable);
+
+        if !path.is_file() {
+            return Err(From::from(format!(
+                "executable '{}' does not exist in {}",
+                executable,
+                target_dir.display()
+            )));
+        }
 
-        Ok(())
+        Ok(Candidate {
+            captures: utils::named_captures(&self.executables, &path.to_string_lossy()),
+            path: PathBuf::from(executable),
+        })
     }
 
     pub fn feature_score(&self, items: &[&str]) -> usize {
@@ -0,0 +1,67 @@
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{ArrayValidation, InstanceType, Metadata, ObjectValidation, Schema, SchemaObject};
+
+fn string(format: &str, description: &str) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::String.into()),
+        format: Some(String::from(format)),
+        metadata: Some(Box::new(Metadata {
+            description: Some(String::from(description)),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }.into()
+}
+
+fn array(items: Schema) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Array.into()),
+        array: Some(Box::new(ArrayValidation {
+            items: Some(items.into()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }.into()
+}
+
+/// A regex as read by `deserialisers::regex`.
+pub fn regex(_: &mut SchemaGenerator) -> Schema {
+    string(
+        "regex",
+        "Regular expression, matched case-insensitively. Named groups are added to the mappings.",
+    )
+}
+
+pub fn regex_array(gen: &mut SchemaGenerator) -> Schema {
+    array(regex(gen))
+}
+
+/// A string that mappings are substituted into, see `Template`.
+pub fn template(_: &mut SchemaGenerator) -> Schema {
+    string(
+        "template",
+        "Template in which {key} and {key:-default} are replaced by mappings, $VAR and ${VAR} by environment variables and {{expression}} by the values of an expression.",
+    )
+}
+
+/// A template that may be left out with `null`.
+pub fn optional_template(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = template(gen).into_object();
+    schema.instance_type = Some(vec![InstanceType::String, InstanceType::Null].into());
+    schema.into()
+}
+
+pub fn template_array(gen: &mut SchemaGenerator) -> Schema {
+    array(template(gen))
+}
+
+pub fn template_map(gen: &mut SchemaGenerator) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        object: Some(Box::new(ObjectValidation {
+            additional_properties: Some(Box::new(template(gen))),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }.into()
+}
@@ -0,0 +1,18 @@
+use regex::Regex;
+
+use serde::Serializer;
+
+/// Writes a regex as its pattern, as read by `deserialisers::regex`.
+pub fn regex<S>(regex: &Regex, serialiser: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialiser.serialize_str(regex.as_str())
+}
+
+pub fn regex_array<S>(regexes: &[Regex], serialiser: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serialiser.collect_seq(regexes.iter().map(Regex::as_str))
+}
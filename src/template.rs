@@ -1,50 +1,219 @@
-use regex::Regex;
-
-use eval::{Expr, Value};
+use expression::{Expression, ExpressionError, Scope};
 
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment<'t> {
+    Text(&'t str),
+    Substitution {
+        raw: &'t str,
+        key: &'t str,
+        default: Option<&'t str>,
+    },
+    Expansion {
+        expr: &'t str,
+        offset: usize,
+    },
+}
 
 pub struct Template<'t> {
     template: &'t str,
-    substitutions: Vec<&'t str>,
-    expansions: Vec<&'t str>,
+    segments: Vec<Segment<'t>>,
+    unterminated: Option<usize>,
 }
 
 impl<'t, 'm> Template<'t> {
     pub fn new(template: &'t str) -> Template<'t> {
-        lazy_static! {
-            static ref RE_SUBSTITUTION : Regex = Regex::new("\\{[_a-zA-Z0-9]*?\\}").unwrap();
-            static ref RE_EXPANSION : Regex = Regex::new("\\{{2}.*?\\}{2}").unwrap();
-        }
+        let (segments, unterminated) = Template::parse(template);
 
         Template {
             template: template,
-            substitutions: RE_SUBSTITUTION
-                .captures_iter(template)
-                .map(|cap| cap.get(0).unwrap().as_str())
-                .collect(),
-            expansions: RE_EXPANSION
-                .captures_iter(template)
-                .map(|cap| cap.get(0).unwrap().as_str())
-                .collect(),
+            segments: segments,
+            unterminated: unterminated,
         }
     }
 
-    pub fn substitute(&self, map: &HashMap<String, String>) -> Result<String, Box<Error>> {
-        let mut substitute = String::from(self.template);
+    /// Splits a template into literal text, `{key}`/`{key:-default}`
+    /// substitutions and `{{expression}}` expansions, along with the offset of
+    /// the first `{{` that is never closed.
+    fn parse(template: &'t str) -> (Vec<Segment<'t>>, Option<usize>) {
+        let bytes = template.as_bytes();
+        let mut segments = Vec::new();
+        let mut unterminated = None;
+        let mut text_start = 0;
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            if bytes[pos] != b'{' {
+                pos += 1;
+                continue;
+            }
 
-        for cap in &self.substitutions {
-            let expr = &cap[1..cap.len() - 1];
+            let segment = if template[pos..].starts_with("{{") {
+                template[pos + 2..].find("}}").map(|end| {
+                    (
+                        Segment::Expansion {
+                            expr: &template[pos + 2..pos + 2 + end],
+                            offset: pos + 2,
+                        },
+                        end + 4,
+                    )
+                })
+            } else if pos > 0 && bytes[pos - 1] == b'$' {
+                // `${...}` is environment variable syntax, not a substitution.
+                None
+            } else {
+                Template::parse_substitution(&template[pos..])
+            };
 
-            if let Some(mapping) = map.get(expr) {
-                substitute = substitute.replace(cap, mapping);
+            if let Some((segment, len)) = segment {
+                if text_start < pos {
+                    segments.push(Segment::Text(&template[text_start..pos]));
+                }
+                segments.push(segment);
+                pos += len;
+                text_start = pos;
             } else {
-                let err = TemplateError::SubstitutionNoFound {
-                    key: String::from(expr),
-                };
+                if template[pos..].starts_with("{{") && unterminated.is_none() {
+                    unterminated = Some(pos);
+                }
+                pos += 1;
+            }
+        }
+
+        if text_start < bytes.len() {
+            segments.push(Segment::Text(&template[text_start..]));
+        }
+
+        (segments, unterminated)
+    }
+
+    fn parse_substitution(input: &'t str) -> Option<(Segment<'t>, usize)> {
+        let key_len = input[1..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(input.len() - 1);
+
+        if key_len == 0 {
+            return None;
+        }
+
+        let key = &input[1..1 + key_len];
+        let rest = &input[1 + key_len..];
+
+        if rest.starts_with('}') {
+            let len = key_len + 2;
+            return Some((
+                Segment::Substitution {
+                    raw: &input[..len],
+                    key: key,
+                    default: None,
+                },
+                len,
+            ));
+        }
+
+        if rest.starts_with(":-") {
+            if let Some(end) = rest.find('}') {
+                let len = 1 + key_len + end + 1;
+                return Some((
+                    Segment::Substitution {
+                        raw: &input[..len],
+                        key: key,
+                        default: Some(&rest[2..end]),
+                    },
+                    len,
+                ));
+            }
+        }
+
+        None
+    }
+
+    /// Returns the mapping keys referenced by the template's substitutions.
+    pub fn keys(&self) -> Vec<&'t str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match *segment {
+                Segment::Substitution { key, .. } => Some(key),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn has_expansions(&self) -> bool {
+        self.segments.iter().any(|segment| match *segment {
+            Segment::Expansion { .. } => true,
+            _ => false,
+        })
+    }
 
-                //return Err(Box::new(err));
+    /// Checks that every `{{` is closed and every expansion parses, reporting
+    /// errors with 1-based columns into the whole template.
+    pub fn validate(&self) -> Result<(), TemplateError> {
+        if let Some(offset) = self.unterminated {
+            return Err(TemplateError::Unterminated {
+                template: String::from(self.template),
+                column: self.template[..offset].chars().count() + 1,
+            });
+        }
+
+        for segment in &self.segments {
+            if let Segment::Expansion { expr, offset } = *segment {
+                self.compile(expr, offset)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn compile(&self, expr: &str, offset: usize) -> Result<Expression, TemplateError> {
+        Expression::parse(expr).map_err(|err| match err {
+            ExpressionError::Parse { column, message } => TemplateError::Expression {
+                template: String::from(self.template),
+                column: self.template[..offset].chars().count() + column,
+                message: message,
+            },
+            ExpressionError::Evaluation { message } => TemplateError::Expression {
+                template: String::from(self.template),
+                column: self.template[..offset].chars().count() + 1,
+                message: message,
+            },
+        })
+    }
+
+    fn lookup<'a>(
+        raw: &'t str,
+        key: &str,
+        default: Option<&'t str>,
+        map: &'a HashMap<String, String>,
+    ) -> &'a str
+    where
+        't: 'a,
+    {
+        match (map.get(key), default) {
+            (Some(mapping), Some(default)) if mapping.is_empty() => default,
+            (Some(mapping), _) => mapping,
+            (None, Some(default)) => default,
+            (None, None) => raw,
+        }
+    }
+
+    pub fn substitute(&self, map: &HashMap<String, String>) -> Result<String, Box<Error>> {
+        let mut substitute = String::new();
+
+        for segment in &self.segments {
+            match *segment {
+                Segment::Text(text) => substitute.push_str(text),
+                Segment::Expansion { expr, .. } => {
+                    substitute.push_str("{{");
+                    substitute.push_str(expr);
+                    substitute.push_str("}}");
+                }
+                Segment::Substitution { raw, key, default } => {
+                    substitute.push_str(Template::lookup(raw, key, default, map));
+                }
             }
         }
 
@@ -59,28 +228,43 @@ impl<'t, 'm> Template<'t> {
     where
         T: ToString,
     {
+        let mut expressions = Vec::new();
+        for segment in &self.segments {
+            if let Segment::Expansion { expr, offset } = *segment {
+                expressions.push(self.compile(expr, offset)?);
+            }
+        }
+
         let mut res = Vec::new();
-        let template = self.substitute(map)?;
 
         for (i, val) in input.iter().map(|val| val.to_string()).enumerate() {
-            let mut template = template.clone();
-
-            for cap in &self.expansions {
-                let expr = Expr::new(&cap[2..cap.len() - 2])
-                    .value("$i", i)
-                    .value("$val", &val)
-                    .exec()?;
-
-                let value = match expr {
-                    Value::Number(val) => Some(val.to_string()),
-                    Value::String(val) => Some(val),
-                    _ => None,
-                };
-
-                if let Some(value) = value {
-                    template = template.replace(cap, &value);
-                } else {
-                    unimplemented!();
+            let scope = Scope {
+                i: i,
+                val: &val,
+                count: input.len(),
+                mappings: map,
+            };
+
+            let mut template = String::new();
+            let mut expressions = expressions.iter();
+
+            for segment in &self.segments {
+                match *segment {
+                    Segment::Text(text) => template.push_str(text),
+                    Segment::Substitution { raw, key, default } => {
+                        template.push_str(Template::lookup(raw, key, default, map));
+                    }
+                    Segment::Expansion { expr, .. } => {
+                        if let Some(expression) = expressions.next() {
+                            let value = expression.evaluate(&scope).map_err(|err| {
+                                TemplateError::Evaluation {
+                                    expression: String::from(expr),
+                                    message: err.to_string(),
+                                }
+                            })?;
+                            template.push_str(&value.to_string());
+                        }
+                    }
                 }
             }
 
@@ -91,8 +275,57 @@ impl<'t, 'm> Template<'t> {
     }
 }
 
-#[derive(Debug, Fail)]
-enum TemplateError {
-    #[fail(display = "Substitution not found: {}", key)]
-    SubstitutionNoFound { key: String },
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplateError {
+    Unterminated {
+        template: String,
+        column: usize,
+    },
+    Expression {
+        template: String,
+        column: usize,
+        message: String,
+    },
+    Evaluation {
+        expression: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TemplateError::Unterminated {
+                ref template,
+                column,
+            } => write!(
+                f,
+                "unterminated '{{{{' at column {} in template '{}'",
+                column, template
+            ),
+            TemplateError::Expression {
+                ref template,
+                column,
+                ref message,
+            } => write!(
+                f,
+                "invalid expression at column {} in template '{}': {}",
+                column, template, message
+            ),
+            TemplateError::Evaluation {
+                ref expression,
+                ref message,
+            } => write!(f, "failed evaluating '{{{{{}}}}}': {}", expression, message),
+        }
+    }
+}
+
+impl Error for TemplateError {
+    fn description(&self) -> &str {
+        match *self {
+            TemplateError::Unterminated { .. } => "unterminated template expansion",
+            TemplateError::Expression { .. } => "invalid template expression",
+            TemplateError::Evaluation { .. } => "failed evaluating template expression",
+        }
+    }
 }
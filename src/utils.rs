@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::error::Error;
 use std::fs;
 use std::path::Path;
@@ -64,10 +65,61 @@ where
     Ok(Vec::new())
 }
 
-pub fn strip_prefix(paths: &mut Vec<PathBuf>, prefix: &PathBuf) -> Result<(), Box<Error>> {
-    for path in paths {
-        *path = PathBuf::from(path.as_path().strip_prefix(&prefix)?.to_str().unwrap());
+/// Returns the named capture groups of the first regex matching `text`, in
+/// group order.
+pub fn named_captures(regexes: &[Regex], text: &str) -> Vec<(String, String)> {
+    for regex in regexes {
+        if let Some(caps) = regex.captures(text) {
+            return regex
+                .capture_names()
+                .filter_map(|name| name)
+                .filter_map(|name| {
+                    caps.name(name)
+                        .map(|val| (String::from(name), String::from(val.as_str())))
+                })
+                .collect();
+        }
+    }
+
+    Vec::new()
+}
+
+/// Compares strings so that runs of digits are ordered by their numeric
+/// value, e.g. `Disk 2` before `Disk 10`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a;
+    let mut b = b;
+
+    while !a.is_empty() && !b.is_empty() {
+        let a_digits = a.starts_with(|c: char| c.is_ascii_digit());
+        let b_digits = b.starts_with(|c: char| c.is_ascii_digit());
+
+        let a_len = a.find(|c: char| c.is_ascii_digit() != a_digits)
+            .unwrap_or_else(|| a.len());
+        let b_len = b.find(|c: char| c.is_ascii_digit() != b_digits)
+            .unwrap_or_else(|| b.len());
+
+        let (a_chunk, b_chunk) = (&a[..a_len], &b[..b_len]);
+
+        let ordering = if a_digits && b_digits {
+            let a_num = a_chunk.trim_start_matches('0');
+            let b_num = b_chunk.trim_start_matches('0');
+            a_num
+                .len()
+                .cmp(&b_num.len())
+                .then_with(|| a_num.cmp(b_num))
+                .then_with(|| a_chunk.len().cmp(&b_chunk.len()))
+        } else {
+            a_chunk.cmp(b_chunk)
+        };
+
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+
+        a = &a[a_len..];
+        b = &b[b_len..];
     }
 
-    Ok(())
+    a.len().cmp(&b.len())
 }
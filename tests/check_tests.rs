@@ -0,0 +1,112 @@
+extern crate rxr;
+use rxr::config::{check_texts, Problem};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+  "extractors": {
+    "p7zip": {
+      "extensions": ["zip", "7z"],
+      "command": { "cmd": "sh", "args": ["{archive}", "{target}"] }
+    },
+    "unzip": {
+      "extensions": ["zip"],
+      "command": { "cmd": "sh", "args": ["{archive}"] }
+    }
+  },
+  "profiles": {
+    "broken": {
+      "command": { "cmd": "rxr-no-such-command", "args": ["{executable}", "{nonsense}"] },
+      "executables": ["(unclosed"],
+      "colour": "blue"
+    },
+    "lonely": {
+      "command": { "cmd": "sh", "args": [] }
+    },
+    "tweaked": {
+      "command": { "cmd": "sh", "args": ["{executable}"] },
+      "executables": [".*\\.exe$"]
+    }
+  }
+}"#;
+
+    const OVERLAY: &str = "profiles:\n  tweaked:\n    executables: ['[']\n";
+
+    fn check(texts: &[(&str, &str)]) -> Vec<Problem> {
+        let texts: Vec<(String, String)> = texts
+            .iter()
+            .map(|&(name, text)| (String::from(name), String::from(text)))
+            .collect();
+
+        check_texts(&texts, None::<&String>).unwrap()
+    }
+
+    fn locations(problems: &[Problem]) -> Vec<(&str, Option<usize>, &str)> {
+        problems
+            .iter()
+            .map(|problem| (problem.file.as_str(), problem.line, problem.path.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn locating_every_kind_of_problem() {
+        let problems = check(&[("rxr.json", CONFIG), ("overlay.yaml", OVERLAY)]);
+
+        assert_eq!(
+            locations(&problems),
+            vec![
+                ("rxr.json", Some(16), "profiles.broken.colour"),
+                ("rxr.json", Some(15), "profiles.broken.executables[0]"),
+                ("rxr.json", Some(14), "profiles.broken.command.cmd"),
+                ("rxr.json", Some(14), "profiles.broken.command.args[1]"),
+                ("rxr.json", Some(18), "profiles.lonely"),
+                ("overlay.yaml", Some(3), "profiles.tweaked.executables[0]"),
+                ("rxr.json", Some(8), "extractors.unzip.extensions"),
+            ]
+        );
+        assert!(problems[0].message.starts_with("unknown key 'colour'"));
+        assert!(problems.iter().all(|problem| !problem.warning));
+        assert!(problems[1].message.starts_with("invalid regex"));
+        assert_eq!(problems[2].message, "'rxr-no-such-command' was not found on PATH");
+        assert_eq!(problems[3].message, "unknown placeholder '{nonsense}'");
+        assert!(problems[4].message.contains("no executables"));
+        assert_eq!(
+            problems[6].message,
+            "extension 'zip' is also handled by extractor 'p7zip'"
+        );
+        assert_eq!(
+            problems[0].to_string(),
+            "rxr.json:16: profiles.broken.colour: unknown key 'colour', expected one of command, executables, features, dosbox"
+        );
+    }
+
+    #[test]
+    fn warning_about_joined_archives() {
+        let problems = check(&[(
+            "rxr.yaml",
+            "extractors:\n  cat:\n    extensions: [txt]\n    command: {cmd: sh, args: ['{archives}']}\n",
+        )]);
+
+        assert_eq!(
+            locations(&problems),
+            vec![("rxr.yaml", Some(4), "extractors.cat.command.args[0]")]
+        );
+        assert!(problems[0].warning);
+        assert!(problems[0].message.contains("{archive_N}"));
+        assert!(problems[0]
+            .to_string()
+            .starts_with("rxr.yaml:4: extractors.cat.command.args[0]: warning: {archives} joins"));
+    }
+
+    #[test]
+    fn reporting_nothing_for_a_valid_config() {
+        let problems = check(&[(
+            "rxr.yaml",
+            "profiles:\n  sh:\n    command: {cmd: sh, args: ['{executable}', '{archive_1}']}\n    executables: ['.*\\.sh$']\n",
+        )]);
+
+        assert_eq!(problems, Vec::new());
+    }
+}
@@ -0,0 +1,634 @@
+#[macro_use]
+extern crate maplit;
+
+extern crate jsonschema;
+extern crate rxr;
+#[macro_use]
+extern crate serde_json;
+use rxr::config::{Compiled, Configuration, Environment, Format, Origin, Persisted, Sidecar};
+
+use jsonschema::JSONSchema;
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+  "extractors": {
+    "zip": {
+      "extensions": ["zip"],
+      "command": { "cmd": "unzip", "args": ["{archive}", "-d", "{target}"] }
+    }
+  },
+  "profiles": {
+    "gzdoom": {
+      "command": { "cmd": "gzdoom", "args": ["-iwad", "{executable}"] },
+      "executables": [".*\\.wad$"]
+    }
+  }
+}"#;
+
+    fn environment(variables: HashMap<&str, &str>) -> Environment {
+        let variables: HashMap<String, String> = variables
+            .into_iter()
+            .map(|(key, val)| (String::from(key), String::from(val)))
+            .collect();
+
+        Environment::from(variables)
+    }
+
+    /// Creates an empty directory for a test under the temp directory.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn building_from_explicit_layers() {
+        let config = Configuration::builder()
+            .args(vec!["-d", "/data", "-t", "/tmp/rxr", "-p", "gzdoom", "doom.zip"])
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.archives, vec![String::from("doom.zip")]);
+        assert_eq!(config.data_dir, "/data");
+        assert_eq!(config.target_dir, "/tmp/rxr/doom");
+        assert_eq!(config.profile, Some(String::from("gzdoom")));
+        assert!(!config.dry_run);
+        assert!(config.extractors.contains_key("zip"));
+        assert_eq!(
+            config.origins.get("profiles.gzdoom"),
+            Some(&vec![Origin::File {
+                path: String::from("rxr.json"),
+                line: Some(9),
+            }])
+        );
+    }
+
+    #[test]
+    fn layering_arguments_over_the_environment() {
+        let config = Configuration::builder()
+            .args(vec!["-t", "/tmp/cli", "doom.zip"])
+            .environment(environment(hashmap! {
+                "RXR_DATA_DIR" => "/data",
+                "RXR_TEMP_DIR" => "/tmp/env",
+                "RXR_PROFILE" => "gzdoom",
+                "RXR_DRY_RUN" => "yes",
+            }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.temp_dir, "/tmp/cli");
+        assert_eq!(config.profile, Some(String::from("gzdoom")));
+        assert!(config.dry_run);
+        assert_eq!(
+            config.origins.get("profile"),
+            Some(&vec![Origin::Environment("RXR_PROFILE")])
+        );
+    }
+
+    #[test]
+    fn rejecting_invalid_booleans() {
+        let err = Configuration::builder()
+            .args(vec!["-d", "/data", "-t", "/tmp/rxr", "doom.zip"])
+            .environment(environment(hashmap! { "RXR_DRY_RUN" => "maybe" }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap_err();
+
+        assert!(err.to_string().contains("RXR_DRY_RUN"));
+    }
+
+    #[test]
+    fn reading_archives_from_the_environment() {
+        let config = Configuration::builder()
+            .environment(environment(hashmap! {
+                "RXR_ARCHIVES" => "doom.zip:doom2.zip",
+                "RXR_DATA_DIR" => "/data",
+                "RXR_TARGET_DIR" => "/games/doom",
+            }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.archives, vec!["doom.zip", "doom2.zip"]);
+        assert_eq!(config.target_dir, "/games/doom");
+        assert_eq!(config.temp_dir, "/games");
+    }
+
+    #[test]
+    fn defaulting_to_xdg_directories() {
+        let config = Configuration::builder()
+            .args(vec!["doom.zip"])
+            .environment(environment(hashmap! {
+                "HOME" => "/home/user",
+                "XDG_CACHE_HOME" => "/cache",
+            }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.data_dir, "/home/user/.local/share/rxr");
+        assert_eq!(config.temp_dir, "/cache/rxr");
+        assert_eq!(config.config, "/home/user/.config/rxr/rxr.json");
+        assert_eq!(
+            config.origins.get("temp_dir"),
+            Some(&vec![Origin::Environment("XDG_CACHE_HOME")])
+        );
+    }
+
+    #[test]
+    fn searching_only_the_system_config_directories_given() {
+        let dir = test_dir("rxr-xdg-config-dirs-tests");
+        fs::create_dir_all(dir.join("rxr")).unwrap();
+        fs::write(dir.join("rxr/rxr.json"), CONFIG).unwrap();
+
+        let build = |compiled: Compiled| {
+            Configuration::builder()
+                .args(vec!["-d", "/data", "-t", "/tmp/rxr", "doom.zip"])
+                .compiled(compiled)
+                .build()
+                .unwrap()
+        };
+        let given = build(Compiled {
+            xdg_config_dirs: Some(dir.to_string_lossy().into_owned()),
+            ..Default::default()
+        });
+        let default = build(Compiled::default());
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(given.config, dir.join("rxr/rxr.json").to_string_lossy());
+        assert!(given.profiles.contains_key("gzdoom"));
+        assert_eq!(default.config, "");
+        assert!(default.profiles.contains_key("msdos"));
+    }
+
+    #[test]
+    fn keeping_the_target_directory_over_xdg_directories() {
+        let config = Configuration::builder()
+            .args(vec!["-o", "/games/doom", "doom.zip"])
+            .environment(environment(hashmap! { "HOME" => "/home/user" }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.target_dir, "/games/doom");
+        assert_eq!(config.temp_dir, "/home/user/.cache/rxr");
+        assert_eq!(
+            config.origins.get("target_dir"),
+            Some(&vec![Origin::CommandLine("--target-directory")])
+        );
+
+        let config = Configuration::builder()
+            .args(vec!["doom.zip"])
+            .environment(environment(hashmap! {
+                "HOME" => "/home/user",
+                "RXR_TARGET_DIR" => "/games/doom",
+            }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.target_dir, "/games/doom");
+    }
+
+    #[test]
+    fn falling_back_to_compiled_and_built_in_configs() {
+        let config = Configuration::builder()
+            .args(vec!["doom.zip"])
+            .compiled(Compiled {
+                data_dir: Some(String::from("/usr/share/rxr")),
+                temp_dir: Some(String::from("/var/tmp/rxr")),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        assert_eq!(config.data_dir, "/usr/share/rxr");
+        assert_eq!(config.target_dir, "/var/tmp/rxr/doom");
+        assert!(config.profiles.contains_key("msdos"));
+        assert!(config.extractors.contains_key("fallback"));
+    }
+
+    #[test]
+    fn reporting_invalid_arguments() {
+        let result = Configuration::builder()
+            .args(vec!["--no-such-option", "doom.zip"])
+            .persisted("rxr.json", CONFIG)
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn building_the_standard_mappings() {
+        let config = Configuration::builder()
+            .args(vec![
+                "-c",
+                "/etc/rxr/rxr.json",
+                "-d",
+                "/data",
+                "-o",
+                "/games/doom",
+                "iso/Doom Disk 1.zip",
+                "iso/Doom Disk 2.zip",
+            ])
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        let mappings = config.mappings(Some("gzdoom"));
+
+        assert_eq!(mappings.get("target"), Some("/games/doom"));
+        assert_eq!(mappings.get("temp_dir"), Some("/games"));
+        assert_eq!(mappings.get("config_dir"), Some("/etc/rxr"));
+        assert_eq!(mappings.get("profile"), Some("gzdoom"));
+        assert_eq!(mappings.get("archive"), Some("iso/Doom Disk 1.zip"));
+        assert_eq!(mappings.get("archive_stem"), Some("Doom Disk 1"));
+        assert_eq!(mappings.get("save_dir"), Some("/data/saves/Doom Disk 1"));
+        assert_eq!(mappings.get("archive_1"), Some("iso/Doom Disk 2.zip"));
+        assert_eq!(mappings.get("archive_stem_1"), Some("Doom Disk 2"));
+        assert_eq!(mappings.get("save_dir_1"), Some("/data/saves/Doom Disk 2"));
+        assert_eq!(
+            mappings.get("archives"),
+            Some("iso/Doom Disk 1.zip iso/Doom Disk 2.zip")
+        );
+        assert_eq!(config.mappings(None).get("profile"), Some(""));
+    }
+
+    #[test]
+    fn merging_config_layers_per_field() {
+        let config = Configuration::builder()
+            .args(vec!["-d", "/data", "-t", "/tmp/rxr", "doom.zip"])
+            .persisted("rxr.json", CONFIG)
+            .persisted(
+                "msdos.json",
+                r#"{
+  "profiles": {
+    "msdos": {
+      "command": {
+        "cmd": "dosbox",
+        "args": ["{executable}"],
+        "evars": { "SDL_VIDEODRIVER": "x11", "SDL_AUDIODRIVER": "alsa" }
+      }
+    }
+  }
+}"#,
+            )
+            .persisted(
+                "tweaks.yaml",
+                r#"profiles:
+  gzdoom: null
+  msdos:
+    command:
+      evars:
+        SDL_VIDEODRIVER: wayland
+        SDL_AUDIODRIVER: null
+"#,
+            )
+            .build()
+            .unwrap();
+
+        let msdos = &config.profiles["msdos"];
+
+        assert!(!config.profiles.contains_key("gzdoom"));
+        assert!(!config.origins.contains_key("profiles.gzdoom"));
+        assert!(config.extractors.contains_key("zip"));
+        assert_eq!(msdos.command.cmd, "dosbox");
+        assert_eq!(msdos.command.args.len(), 1);
+        assert_eq!(
+            msdos.command.evars,
+            hashmap! { String::from("SDL_VIDEODRIVER") => String::from("wayland") }
+        );
+        assert_eq!(config.origins["profiles.msdos"].len(), 2);
+    }
+
+    #[test]
+    fn reading_drop_ins_of_a_config_directory() {
+        let dir = test_dir("rxr-drop-in-tests");
+        let dir_arg = dir.to_string_lossy().into_owned();
+        fs::create_dir_all(dir.join("profiles.d")).unwrap();
+        fs::create_dir_all(dir.join("extractors.d")).unwrap();
+
+        fs::write(dir.join("rxr.json"), CONFIG).unwrap();
+        fs::write(
+            dir.join("profiles.d/dosbox.json"),
+            r#"{"command": {"cmd": "dosbox", "args": ["{executable}"]}}"#,
+        ).unwrap();
+        fs::write(
+            dir.join("profiles.d/gzdoom.yaml"),
+            "override: true\ncommand:\n  evars: {SDL_VIDEODRIVER: x11}\n",
+        ).unwrap();
+
+        let build = || {
+            Configuration::builder()
+                .args(vec![
+                    "--config-directory",
+                    &dir_arg,
+                    "-d",
+                    "/data",
+                    "-t",
+                    "/tmp/rxr",
+                    "doom.zip",
+                ])
+                .build()
+        };
+        let config = build().unwrap();
+
+        assert_eq!(config.profiles["dosbox"].command.cmd, "dosbox");
+        assert_eq!(config.profiles["gzdoom"].command.cmd, "gzdoom");
+        assert_eq!(config.profiles["gzdoom"].command.evars["SDL_VIDEODRIVER"], "x11");
+        assert_eq!(
+            config.origins["profiles.gzdoom"][1],
+            Origin::File {
+                path: dir.join("profiles.d/gzdoom.yaml").to_string_lossy().into_owned(),
+                line: None,
+            }
+        );
+
+        fs::write(
+            dir.join("extractors.d/zip.toml"),
+            "extensions = [\"zip\"]\ncommand = { cmd = \"7z\", args = [\"x\", \"{archive}\"] }\n",
+        ).unwrap();
+
+        let err = build().unwrap_err();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(err.to_string().contains("extractor 'zip'"));
+        assert!(err.to_string().contains("already defined"));
+    }
+
+    #[test]
+    fn reading_toml_and_yaml_configs_and_drop_ins() {
+        let dir = test_dir("rxr-format-tests");
+        fs::create_dir_all(dir.join("profiles.d")).unwrap();
+        fs::create_dir_all(dir.join("extractors.d")).unwrap();
+
+        fs::write(
+            dir.join("rxr.toml"),
+            r#"[extractors.zip]
+extensions = ["zip"]
+command = { cmd = "unzip", args = ["{archive}", "-d", "{target}"] }
+
+[profiles.gzdoom]
+executables = ['.*\.wad$']
+features = [{ pattern = '(?P<episode>e\d)m\d', weight = 2 }]
+command = { cmd = "gzdoom", args = ["-iwad", "{executable}"] }
+"#,
+        ).unwrap();
+        fs::write(
+            dir.join("overlay.yaml"),
+            "profiles:\n  gzdoom:\n    command:\n      evars: {SDL_VIDEODRIVER: x11}\n",
+        ).unwrap();
+        fs::write(
+            dir.join("profiles.d/dosbox.yml"),
+            "command: {cmd: dosbox, args: ['{executable}']}\nexecutables: ['.*\\.exe$']\n",
+        ).unwrap();
+        fs::write(
+            dir.join("extractors.d/rar.toml"),
+            "extensions = [\"rar\"]\ncommand = { cmd = \"unrar\", args = [\"x\", \"{archive}\"] }\n",
+        ).unwrap();
+
+        let persisted = Persisted::read_layers(
+            &[dir.join("rxr.toml"), dir.join("overlay.yaml")],
+            Some(&dir),
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        let persisted = persisted.unwrap();
+        let extractors = persisted.extractors.unwrap();
+        let profiles = persisted.profiles.unwrap();
+        let gzdoom = &profiles["gzdoom"];
+
+        assert!(extractors["zip"].can_extract(&"doom.zip"));
+        assert!(extractors["rar"].can_extract(&"doom.rar"));
+        assert_eq!(gzdoom.command.cmd, "gzdoom");
+        assert_eq!(gzdoom.command.evars["SDL_VIDEODRIVER"], "x11");
+        assert!(gzdoom.executables[0].is_match("DOOM2.WAD"));
+        assert_eq!(gzdoom.features[0].score("E1M1.LMP"), 2);
+        assert!(profiles["dosbox"].executables[0].is_match("KEEN.EXE"));
+    }
+
+    #[test]
+    fn naming_the_entry_with_an_invalid_regex() {
+        let err = Configuration::builder()
+            .args(vec!["-d", "/data", "-t", "/tmp/rxr", "doom.zip"])
+            .persisted("rxr.json", CONFIG)
+            .persisted("broken.yaml", "profiles:\n  broken:\n    features: [{pattern: '('}]\n")
+            .build()
+            .unwrap_err();
+
+        assert!(err.to_string().contains("profile 'broken'"));
+        assert!(err.to_string().contains("regex"));
+    }
+
+    #[test]
+    fn naming_the_null_that_toml_cannot_express() {
+        let value = json!({ "profiles": { "gzdoom": { "dosbox": null } } });
+
+        let err = Format::Toml.render(&value).unwrap_err();
+
+        assert!(err.to_string().contains("profiles.gzdoom.dosbox"));
+        assert!(Format::Yaml.render(&value).is_ok());
+    }
+
+    #[test]
+    fn tracking_the_origin_of_every_layer() {
+        let config = Configuration::builder()
+            .args(vec!["-p", "gzdoom", "doom.zip"])
+            .environment(environment(hashmap! {
+                "HOME" => "/home/user",
+                "RXR_DATA_DIR" => "/data",
+            }))
+            .persisted("rxr.json", CONFIG)
+            .compiled(Compiled {
+                temp_dir: Some(String::from("/var/tmp/rxr")),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let origin = |key: &str| config.origins[key].clone();
+
+        assert_eq!(origin("profile"), vec![Origin::CommandLine("--profile")]);
+        assert_eq!(origin("data_dir"), vec![Origin::Environment("RXR_DATA_DIR")]);
+        assert_eq!(origin("temp_dir"), vec![Origin::Compiled("RXR_TEMP_DIR")]);
+        assert_eq!(origin("target_dir"), vec![Origin::Derived("temp_dir and the archives")]);
+        assert_eq!(origin("config"), vec![Origin::Environment("HOME")]);
+        assert_eq!(
+            origin("extractors.zip"),
+            vec![Origin::File {
+                path: String::from("rxr.json"),
+                line: Some(3),
+            }]
+        );
+    }
+
+    #[test]
+    fn locating_entries_in_every_format() {
+        let config = Configuration::builder()
+            .args(vec!["-d", "/data", "-t", "/tmp/rxr", "doom.zip"])
+            .persisted(
+                "base.yaml",
+                r#"# The profiles follow the extractors.
+extractors:
+  gzdoom:
+    extensions: [wad]
+    command: {cmd: cp, args: ["{archive}", "{target}"]}
+profiles:
+  gzdoom:
+    command: {cmd: gzdoom, args: ["{executable}"]}
+"#,
+            )
+            .persisted(
+                "overlay.toml",
+                r#"[profiles]
+dosbox = { command = { cmd = "dosbox", args = [] } }
+
+[profiles.gzdoom.command.evars]
+SDL_VIDEODRIVER = "x11"
+"#,
+            )
+            .build()
+            .unwrap();
+
+        let file = |path: &str, line: usize| Origin::File {
+            path: String::from(path),
+            line: Some(line),
+        };
+
+        assert_eq!(config.origins["extractors.gzdoom"], vec![file("base.yaml", 3)]);
+        assert_eq!(
+            config.origins["profiles.gzdoom"],
+            vec![file("base.yaml", 7), file("overlay.toml", 4)]
+        );
+        assert_eq!(config.origins["profiles.dosbox"], vec![file("overlay.toml", 2)]);
+    }
+
+    #[test]
+    fn validating_complete_and_overlay_configs_against_the_schema() {
+        let schema = serde_json::to_value(Persisted::schema()).unwrap();
+        let schema = JSONSchema::compile(&schema).unwrap();
+        let parse = |text: &str| -> Value { serde_json::from_str(text).unwrap() };
+
+        assert!(schema.is_valid(&parse(include_str!("../config/default.json"))));
+        assert!(schema.is_valid(&parse(CONFIG)));
+        assert!(schema.is_valid(&parse(r#"{"profiles": {"msdos": null}}"#)));
+        assert!(schema.is_valid(&parse(
+            r#"{"profiles": {"msdos": {"command": {"evars": {"SDL_VIDEODRIVER": "x11"}}}}}"#
+        )));
+
+        assert!(!schema.is_valid(&parse(r#"{"profiles": {"msdos": {"command": {"cmd": 3}}}}"#)));
+        assert!(!schema.is_valid(&parse(r#"{"profiles": {"msdos": {"executable": []}}}"#)));
+    }
+
+    #[test]
+    fn pinning_overrides_with_sidecars() {
+        let dir = env::temp_dir().join("rxr-sidecar-tests");
+        let target = dir.join("target");
+        let archive = dir.join("doom.zip").to_string_lossy().into_owned();
+        fs::create_dir_all(&target).unwrap();
+
+        fs::write(
+            format!("{}.rxr.json", archive),
+            r#"{"profile": "gzdoom", "args": ["-fast"], "evars": {"A": "archive"}}"#,
+        ).unwrap();
+        fs::write(
+            target.join(".rxr.yaml"),
+            "executable: DOOM2.WAD\nargs: [\"-nomusic\"]\nevars: {A: root, B: root}\n",
+        ).unwrap();
+
+        let config = Configuration::builder()
+            .args(vec!["-d", "/data", "-o", target.to_str().unwrap(), archive.as_str()])
+            .environment(environment(hashmap! { "RXR_PROFILE" => "msdos" }))
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            config.sidecar,
+            Sidecar {
+                profile: Some(String::from("gzdoom")),
+                executable: Some(String::from("DOOM2.WAD")),
+                evars: hashmap! {
+                    String::from("A") => String::from("archive"),
+                    String::from("B") => String::from("root"),
+                },
+                args: vec![String::from("-nomusic"), String::from("-fast")],
+                dosbox: None,
+            }
+        );
+        assert_eq!(config.profile, Some(String::from("gzdoom")));
+        assert_eq!(
+            config.origins.get("profile"),
+            Some(&vec![Origin::File {
+                path: format!("{}.rxr.json", archive),
+                line: None,
+            }])
+        );
+        assert_eq!(config.origins["sidecar"].len(), 2);
+    }
+
+    #[test]
+    fn preferring_the_command_line_over_sidecars() {
+        let dir = env::temp_dir().join("rxr-sidecar-command-line-tests");
+        let archive = dir.join("doom.zip").to_string_lossy().into_owned();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(format!("{}.rxr.json", archive), r#"{"profile": "gzdoom"}"#).unwrap();
+
+        let config = Configuration::builder()
+            .args(vec!["-d", "/data", "-t", "/tmp/rxr", "-p", "msdos", archive.as_str()])
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(config.profile, Some(String::from("msdos")));
+        assert_eq!(
+            config.origins.get("profile"),
+            Some(&vec![Origin::CommandLine("--profile")])
+        );
+    }
+
+    #[test]
+    fn refusing_sidecars_that_cannot_apply() {
+        let dir = test_dir("rxr-sidecar-refusal-tests");
+        let archive = dir.join("doom.zip").to_string_lossy().into_owned();
+        let build = || {
+            Configuration::builder()
+                .args(vec!["-d", "/data", "-o", dir.to_str().unwrap(), archive.as_str()])
+                .persisted("rxr.json", CONFIG)
+                .build()
+        };
+
+        fs::write(format!("{}.rxr.json", archive), r#"{"args": ["{{$i +}}"]}"#).unwrap();
+        let invalid = build().unwrap_err().to_string();
+
+        fs::write(
+            format!("{}.rxr.json", archive),
+            r#"{"dosbox": {"settings": {"cpu": {"cycles": "max"}}}}"#,
+        ).unwrap();
+        let config = build().unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(invalid.starts_with("invalid template in the sidecar files"));
+        assert!(config.sidecar.apply(&config.profiles["gzdoom"]).is_err());
+    }
+}
@@ -2,11 +2,13 @@
 extern crate maplit;
 
 extern crate rxr;
-use rxr::dosbox_config::DosboxConfig;
+extern crate serde_json;
+use rxr::dosbox_config::{gog_base, AutoexecCommand, AutoexecMode, Dialect, DosboxConfig, Document,
+                         Format, GogRole, IssueKind, MergeOptions, ParseError, SectionStrategy};
 
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[cfg(test)]
 mod tests {
@@ -77,8 +79,8 @@ mod tests {
         let expected = DosboxConfig {
             autoexec: vec![
                 "@echo off",
-                "mount c \"../game\"",
-                "imgmount d \"../game/game.iso\" -t iso -fs iso",
+                "mount c \"..\\game\"",
+                "imgmount d \"..\\game\\game.iso\" -t iso -fs iso",
                 "c:",
                 "cls",
                 "game.exe",
@@ -125,4 +127,504 @@ mod tests {
         assert_eq!(merged, file);
         fs::remove_file(&config_path).unwrap();
     }
+
+    const GOG_CONFIG: &str = "# This is the configurationfile for DOSBox 0.74.
+# Lines starting with a # are commentlines.
+
+[sdl]
+#       fullscreen: Start dosbox directly in fullscreen.
+fullscreen = false
+output     = surface
+
+[cpu]
+# core: CPU Core used in emulation.
+core=auto
+cycles=fixed 5000
+
+[autoexec]
+# Lines in this section will be run at startup.
+@echo off
+mount C \"..\"
+c:
+game.exe
+";
+
+    #[test]
+    fn document_round_trips_unchanged() {
+        let lines: Vec<&str> = GOG_CONFIG.lines().collect();
+        let document = Document::parse(lines.as_slice()).unwrap();
+
+        assert_eq!(document.to_string(), GOG_CONFIG);
+        assert_eq!(document.get("sdl", "output"), Some("surface"));
+        assert_eq!(document.get("cpu", "cycles"), Some("fixed 5000"));
+    }
+
+    #[test]
+    fn document_rewrites_only_changed_values() {
+        let lines: Vec<&str> = GOG_CONFIG.lines().collect();
+        let mut document = Document::parse(lines.as_slice()).unwrap();
+
+        let overrides = DosboxConfig::parse(&[
+            "[sdl]",
+            "output=opengl",
+            "fullscreen=false",
+            "[cpu]",
+            "core=dynamic",
+            "[mixer]",
+            "rate=44100",
+        ])
+        .unwrap();
+        let merged = document.config().merge(&overrides);
+        document.apply(&merged);
+
+        let expected = GOG_CONFIG
+            .replace("output     = surface", "output     = opengl")
+            .replace(
+                "core=auto\ncycles=fixed 5000\n",
+                "core=dynamic\ncycles=fixed 5000\n\n[mixer]\nrate = 44100\n",
+            );
+
+        assert_eq!(document.to_string(), expected);
+    }
+
+    #[test]
+    fn validating_settings_against_the_schema() {
+        let config = DosboxConfig::parse(&[
+            "[cpu]",
+            "core=dynamc",
+            "cycles=max 80% limit 20000",
+            "[sblaster]",
+            "irq=17",
+            "oplmode=opl3",
+            "[render]",
+            "scaler=hq3x forced",
+            "framskip=1",
+            "[glide]",
+            "glide=true",
+        ])
+        .unwrap();
+
+        let issues = config.validate();
+        let kinds: Vec<(&str, &str, &IssueKind)> = issues
+            .iter()
+            .map(|issue| (issue.section.as_str(), issue.key.as_str(), &issue.kind))
+            .collect();
+
+        assert_eq!(kinds.len(), 4);
+        assert_eq!((kinds[0].0, kinds[0].1), ("cpu", "core"));
+        assert_eq!(kinds[1], ("glide", "glide", &IssueKind::UnknownSection));
+        assert_eq!(kinds[2], ("render", "framskip", &IssueKind::UnknownKey));
+        assert_eq!(
+            kinds[3],
+            (
+                "sblaster",
+                "irq",
+                &IssueKind::InvalidValue(String::from("expected one of: 7, 5, 3, 9, 10, 11, 12"))
+            )
+        );
+        assert!(!issues[0].is_unknown());
+        assert!(issues[1].is_unknown());
+    }
+
+    #[test]
+    fn merging_with_strategies() {
+        let base = DosboxConfig::parse(&[
+            "[cpu]",
+            "core=dynamic",
+            "cycles=max",
+            "[sdl]",
+            "fullscreen=true",
+            "output=opengl",
+            "[autoexec]",
+            "mount C .",
+            "C:",
+        ])
+        .unwrap();
+        let single = DosboxConfig::parse(&[
+            "[cpu]",
+            "core=normal",
+            "cputype=386",
+            "[sdl]",
+            "output=surface",
+            "[autoexec]",
+            "mount c .",
+            "c:",
+            "game.exe",
+        ])
+        .unwrap();
+
+        let options = MergeOptions {
+            strategy: SectionStrategy::ReplaceSection,
+            sections: hashmap!{ String::from("cpu") => SectionStrategy::KeepSource },
+            autoexec: AutoexecMode::Dedupe,
+        };
+        let merged = base.merge_with(&single, &options);
+
+        assert_eq!(merged.autoexec, vec!["mount C .", "C:", "game.exe"]);
+        assert_eq!(
+            merged.settings["cpu"],
+            to_owned_map(hashmap!{ "core" => "dynamic", "cycles" => "max", "cputype" => "386" })
+        );
+        assert_eq!(
+            merged.settings["sdl"],
+            to_owned_map(hashmap!{ "output" => "surface" })
+        );
+
+        let replaced = base.merge_with(
+            &single,
+            &MergeOptions {
+                autoexec: AutoexecMode::Replace,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(replaced.autoexec, single.autoexec);
+        assert_eq!(replaced.settings["cpu"]["core"], "normal");
+        assert_eq!(replaced.settings["sdl"]["fullscreen"], "true");
+
+        let kept = base.merge_with(
+            &single,
+            &MergeOptions {
+                sections: hashmap!{ String::from("CPU") => SectionStrategy::KeepSource },
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(kept.settings["cpu"]["core"], "dynamic");
+        assert_eq!(kept.settings["sdl"]["output"], "surface");
+    }
+
+    #[test]
+    fn merging_a_mixed_case_inline_config() {
+        let base = DosboxConfig::parse(&["[cpu]", "cycles=auto", "core=auto"]).unwrap();
+        let inline: DosboxConfig = serde_json::from_str(
+            r#"{ "settings": { "CPU": { "Cycles": "fixed 5000" }, "Cpu": { "core": "dynamic" } } }"#,
+        ).unwrap();
+
+        assert_eq!(
+            base.merge(&inline).export(Format::Conf).unwrap(),
+            "[cpu]\ncore=dynamic\ncycles=fixed 5000\n[autoexec]\n"
+        );
+    }
+
+    #[test]
+    fn parsing_and_rebasing_autoexec_commands() {
+        let mut config = DosboxConfig::parse(&[
+            "[autoexec]",
+            "@echo off",
+            "mount C \"..\"",
+            "imgmount d \"..\\game.ins\" -t iso -fs iso",
+            "C:",
+            "cd\\GAME",
+            "C:\\GAME\\RUN.EXE -nosound",
+            "exit",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.commands(),
+            vec![
+                AutoexecCommand::Echo {
+                    silent: true,
+                    text: String::from("off"),
+                },
+                AutoexecCommand::Mount {
+                    drive: String::from("C"),
+                    path: String::from(".."),
+                    options: vec![],
+                },
+                AutoexecCommand::ImgMount {
+                    drive: String::from("d"),
+                    paths: vec![String::from("..\\game.ins")],
+                    options: vec!["-t", "iso", "-fs", "iso"]
+                        .iter()
+                        .map(|s| String::from(*s))
+                        .collect(),
+                },
+                AutoexecCommand::Drive(String::from("C")),
+                AutoexecCommand::Cd(String::from("\\GAME")),
+                AutoexecCommand::Call {
+                    program: String::from("C:\\GAME\\RUN.EXE"),
+                    args: String::from("-nosound"),
+                },
+                AutoexecCommand::Call {
+                    program: String::from("exit"),
+                    args: String::new(),
+                },
+            ]
+        );
+
+        config.rebase_mounts(&PathBuf::from("/games/target/DOSBOX"));
+
+        assert_eq!(
+            config.autoexec,
+            vec![
+                "@echo off",
+                "mount C \"/games/target\"",
+                "imgmount d \"/games/target/game.ins\" -t iso -fs iso",
+                "C:",
+                "cd\\GAME",
+                "C:\\GAME\\RUN.EXE -nosound",
+                "exit",
+            ]
+        );
+    }
+
+    #[test]
+    fn generating_a_launcher_config() {
+        let config = DosboxConfig::launcher(
+            &PathBuf::from("/games/target"),
+            &PathBuf::from("GAME/BIN/RUN.EXE"),
+        );
+
+        assert_eq!(
+            config.autoexec,
+            vec![
+                "@echo off",
+                "mount C \"/games/target\"",
+                "C:",
+                "cd \\GAME\\BIN",
+                "RUN.EXE",
+                "exit",
+            ]
+        );
+        assert!(config.settings.is_empty());
+    }
+
+    #[test]
+    fn document_unsets_every_occurrence() {
+        let mut document = Document::parse(&[
+            "[cpu]",
+            "# first",
+            "core=auto",
+            "cycles=max",
+            "core=dynamic",
+        ])
+        .unwrap();
+
+        assert!(document.unset("cpu", "core"));
+        assert!(!document.unset("cpu", "core"));
+        assert_eq!(document.get("cpu", "core"), None);
+        assert_eq!(document.to_string(), "[cpu]\n# first\ncycles=max\n");
+    }
+
+    #[test]
+    fn removing_settings_only_when_applying_a_translation() {
+        let lines = ["[sdl]", "sensitivity=100", "[render]", "scaler=normal2x"];
+        let mut document = Document::parse(&lines).unwrap();
+        let (translated, _) = document
+            .config()
+            .translate(Dialect::Dosbox, Dialect::Staging);
+
+        document.apply(&translated);
+
+        assert_eq!(document.get("sdl", "sensitivity"), Some("100"));
+        assert_eq!(document.get("render", "scaler"), Some("normal2x"));
+        assert_eq!(document.get("mouse", "mouse_sensitivity"), Some("100"));
+
+        let mut document = Document::parse(&lines).unwrap();
+        document.apply_translated(&translated);
+
+        assert_eq!(document.get("sdl", "sensitivity"), None);
+        assert_eq!(document.get("render", "scaler"), None);
+        assert_eq!(document.config().settings, translated.settings);
+    }
+
+    #[test]
+    fn translating_between_dialects() {
+        let gog = DosboxConfig::parse(&[
+            "[sdl]",
+            "output=overlay",
+            "sensitivity=100",
+            "[render]",
+            "scaler=normal2x",
+            "[glide]",
+            "glide=true",
+        ])
+        .unwrap();
+        let staging = DosboxConfig::parse(&["[render]", "glshader=crt-auto"]).unwrap();
+
+        assert_eq!(Dialect::detect(&gog), Dialect::Dosbox);
+        assert_eq!(Dialect::detect(&staging), Dialect::Staging);
+
+        let (translated, issues) = gog.translate(Dialect::Dosbox, Dialect::Staging);
+
+        assert_eq!(
+            translated.settings,
+            hashmap!{
+                String::from("sdl") => to_owned_map(hashmap!{ "output" => "texture" }),
+                String::from("mouse") => to_owned_map(hashmap!{ "mouse_sensitivity" => "100" }),
+                String::from("glide") => to_owned_map(hashmap!{ "glide" => "true" }),
+            }
+        );
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].section.as_str(), issues[0].key.as_str()), ("render", "scaler"));
+        assert_eq!(issues[0].kind, IssueKind::Unsupported(Dialect::Staging));
+
+        let (back, issues) = translated.translate(Dialect::Staging, Dialect::Dosbox);
+
+        assert!(issues.is_empty());
+        assert_eq!(back.settings["sdl"]["output"], "surface");
+        assert_eq!(back.settings["sdl"]["sensitivity"], "100");
+
+        let issues = translated.validate_as(Dialect::Staging);
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::UnknownSection);
+    }
+
+    #[test]
+    fn parsing_is_case_insensitive_and_tolerates_duplicates() {
+        let document = Document::parse(&[
+            "% DOSBox-X style comment",
+            "[CPU]",
+            "Core=normal",
+            "cycles = fixed 3000   # inline comment",
+            "[cpu] # again",
+            "core=dynamic",
+            "[sdl]",
+            "mapperfile=",
+        ])
+        .unwrap();
+
+        assert_eq!(document.get("cpu", "CORE"), Some("dynamic"));
+        assert_eq!(
+            document.occurrences("cpu", "core"),
+            vec![(3, "normal"), (6, "dynamic")]
+        );
+        assert_eq!(
+            document.duplicates(),
+            vec![(String::from("cpu"), String::from("core"), vec![3, 6])]
+        );
+
+        let config = document.config();
+
+        assert_eq!(
+            config.settings,
+            hashmap!{
+                String::from("cpu") => to_owned_map(hashmap!{
+                    "core" => "dynamic",
+                    "cycles" => "fixed 3000"
+                }),
+                String::from("sdl") => to_owned_map(hashmap!{ "mapperfile" => "" }),
+            }
+        );
+        assert!(config.validate().is_empty());
+        assert_eq!(config.get("CPU", "Core"), Some(&String::from("dynamic")));
+        assert_eq!(config.get("cpu", "cputype"), None);
+    }
+
+    #[test]
+    fn reporting_malformed_lines() {
+        let error = |lines: &[&str]| {
+            let err = Document::parse(lines).unwrap_err();
+            let err = err.downcast_ref::<ParseError>().unwrap();
+            (err.line, err.column, err.message.clone())
+        };
+
+        assert_eq!(
+            error(&["core=auto"]),
+            (
+                1,
+                1,
+                String::from("expected a section header before the first setting")
+            )
+        );
+        assert_eq!(
+            error(&["[cpu]", "  core auto=normal"]),
+            (2, 7, String::from("unexpected whitespace in the key"))
+        );
+        assert_eq!(
+            error(&["[cpu]", "cycles"]),
+            (2, 7, String::from("expected '=' after the key"))
+        );
+        assert_eq!(
+            error(&["[cpu]", "=auto"]),
+            (2, 1, String::from("expected a key before '='"))
+        );
+        assert_eq!(
+            error(&["", "[cpu"]),
+            (2, 5, String::from("expected ']' after the section name"))
+        );
+    }
+
+    #[test]
+    fn exporting_and_importing_structured_configs() {
+        let config = DosboxConfig {
+            autoexec: vec![String::from("mount C \"game\""), String::from("C:")],
+            settings: hashmap!{
+                String::from("sdl") => to_owned_map(hashmap!{ "output" => "opengl", "fullscreen" => "true" }),
+                String::from("cpu") => to_owned_map(hashmap!{ "cycles" => "max" }),
+            },
+        };
+
+        assert_eq!(
+            config.export(Format::Toml).unwrap(),
+            "autoexec = [\"mount C \\\"game\\\"\", \"C:\"]\n\
+             [settings.cpu]\n\
+             cycles = \"max\"\n\n\
+             [settings.sdl]\n\
+             fullscreen = \"true\"\n\
+             output = \"opengl\"\n"
+        );
+
+        for format in &[Format::Conf, Format::Json, Format::Toml] {
+            let exported = config.export(*format).unwrap();
+            assert_eq!(DosboxConfig::import(&exported, *format).unwrap(), config);
+        }
+
+        assert_eq!(
+            DosboxConfig::import("{ \"settings\": { \"cpu\": { \"core\": \"auto\" } } }", Format::Json)
+                .unwrap(),
+            DosboxConfig {
+                autoexec: Vec::new(),
+                settings: hashmap!{ String::from("cpu") => to_owned_map(hashmap!{ "core" => "auto" }) },
+            }
+        );
+
+        let imported = DosboxConfig::import("[settings.CPU]\nCore = \"auto\"\n", Format::Toml).unwrap();
+        let merged = DosboxConfig::parse(&["[cpu]", "core=dynamic"]).unwrap().merge(&imported);
+
+        assert_eq!(
+            merged.settings,
+            hashmap!{ String::from("cpu") => to_owned_map(hashmap!{ "core" => "auto" }) }
+        );
+        assert_eq!(Format::from_path(&"game.TOML"), Format::Toml);
+        assert_eq!(Format::from_path(&"dosbox-0.74.conf"), Format::Conf);
+    }
+
+    #[test]
+    fn pairing_gog_configs() {
+        let files: Vec<PathBuf> = vec![
+            "app/dosbox_keen.conf",
+            "app/dosbox_keen_single.conf",
+            "app/dosbox_keen_setup.conf",
+            "app/dosbox_keen_client.conf",
+            "app/goggame.ico",
+            "extras/dosbox_other.conf",
+        ].into_iter()
+            .map(PathBuf::from)
+            .collect();
+
+        assert_eq!(GogRole::of(Path::new("app/DOSBOXKeen.conf")), Some(GogRole::Base));
+        assert_eq!(GogRole::of(Path::new("app/dosboxKeen_Single.conf")), Some(GogRole::Game));
+        assert_eq!(GogRole::of(Path::new("app/dosboxKeen_client.conf")), Some(GogRole::Game));
+        assert_eq!(GogRole::of(Path::new("app/dosboxKeen_setup.conf")), Some(GogRole::Setup));
+        assert_eq!(GogRole::of(Path::new("app/keen.conf")), None);
+        assert_eq!(GogRole::of(Path::new("app/dosbox.exe")), None);
+
+        assert_eq!(
+            gog_base(Path::new("app/dosbox_keen_single.conf"), &files),
+            Some(&files[0])
+        );
+        assert_eq!(
+            gog_base(Path::new("app/dosbox_keen_setup.conf"), &files),
+            Some(&files[0])
+        );
+        assert_eq!(
+            gog_base(Path::new("app/dosboxKeen2_single.conf"), &files),
+            Some(&files[0])
+        );
+        assert_eq!(gog_base(Path::new("other/dosbox_keen_single.conf"), &files), None);
+    }
 }
@@ -0,0 +1,119 @@
+#[macro_use]
+extern crate maplit;
+
+extern crate rxr;
+use rxr::command::{Argument, Command};
+use rxr::config::{ConfigError, Environment};
+
+use std::env;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn environment() -> Environment {
+        Environment::from(hashmap! {
+            String::from("HOME") => String::from("/home/user"),
+            String::from("GAME") => String::from("doom"),
+            String::from("EMPTY") => String::new(),
+        })
+    }
+
+    #[test]
+    fn resolving_variables() {
+        let environment = environment();
+        let resolve = |template: &str| environment.resolve(template).unwrap();
+
+        assert_eq!(resolve("$GAME/${GAME}.wad"), "doom/doom.wad");
+        assert_eq!(resolve("$$GAME costs $$5"), "$GAME costs $5");
+        assert_eq!(resolve("${MISSING:-freedoom}"), "freedoom");
+        assert_eq!(resolve("${EMPTY:-freedoom}"), "freedoom");
+        assert_eq!(resolve("${GAME:-freedoom}"), "doom");
+        assert_eq!(resolve("trailing $"), "trailing $");
+    }
+
+    #[test]
+    fn resolving_the_home_directory() {
+        let environment = environment();
+        let resolve = |template: &str| environment.resolve(template).unwrap();
+
+        assert_eq!(resolve("~"), "/home/user");
+        assert_eq!(resolve("~/games/$GAME"), "/home/user/games/doom");
+        assert_eq!(resolve("games/~"), "games/~");
+        assert_eq!(resolve("~user"), "~user");
+    }
+
+    #[test]
+    fn leaving_mappings_and_expansions_alone() {
+        let environment = environment();
+
+        assert_eq!(
+            environment.resolve("{target}/$GAME {{${MISSING}$i}}").unwrap(),
+            "{target}/doom {{${MISSING}$i}}"
+        );
+    }
+
+    #[test]
+    fn reporting_undefined_and_malformed_variables() {
+        let environment = environment();
+
+        match environment.resolve("$GAME/$MISSING") {
+            Err(ConfigError::UndefinedVariable { name, template }) => {
+                assert_eq!(name, "MISSING");
+                assert_eq!(template, "$GAME/$MISSING");
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        match Environment::default().resolve("~/games") {
+            Err(ConfigError::UndefinedVariable { name, .. }) => assert_eq!(name, "HOME"),
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        for template in &["${GAME", "${}", "${GAME NAME}"] {
+            match environment.resolve(template) {
+                Err(ConfigError::MalformedVariable { .. }) => {}
+                result => panic!("unexpected result for '{}': {:?}", template, result),
+            }
+        }
+    }
+
+    #[test]
+    fn keeping_process_variables_unexpanded() {
+        env::set_var("RXR_TEST_UNEXPANDED", "${HOME}/$$5");
+
+        assert_eq!(
+            Environment::current().variable("RXR_TEST_UNEXPANDED"),
+            Some(&String::from("${HOME}/$$5"))
+        );
+    }
+
+    #[test]
+    fn applying_the_environment_to_a_command() {
+        let mut command = Command {
+            cmd: String::from("~/bin/${ENGINE:-gzdoom}"),
+            args: vec![
+                Argument::Value(String::from("$GAME.wad")),
+                Argument::Conditional {
+                    when: String::from("conf"),
+                    args: vec![String::from("-config"), String::from("~/$GAME.ini")],
+                },
+            ],
+            evars: hashmap! { String::from("DOOMWADDIR") => String::from("~/wads/$$GAME") },
+            wd: String::from("{target}"),
+        };
+
+        command.apply_environment(&environment()).unwrap();
+
+        assert_eq!(command.cmd, "/home/user/bin/gzdoom");
+        assert_eq!(
+            command.args.iter().flat_map(Argument::values).collect::<Vec<&String>>(),
+            vec!["doom.wad", "-config", "/home/user/doom.ini"]
+        );
+        assert_eq!(command.evars["DOOMWADDIR"], "/home/user/wads/$GAME");
+        assert_eq!(command.wd, "{target}");
+
+        command.cmd = String::from("$MISSING");
+        assert!(command.apply_environment(&environment()).is_err());
+    }
+}
@@ -0,0 +1,96 @@
+#[macro_use]
+extern crate maplit;
+
+extern crate rxr;
+use rxr::expression::{Expression, ExpressionError, Scope, Value};
+
+use std::collections::HashMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(expr: &str) -> Result<Value, ExpressionError> {
+        let mappings: HashMap<String, String> = hashmap! {
+            String::from("disk") => String::from("2"),
+            String::from("name") => String::from("game"),
+        };
+
+        let scope = Scope {
+            i: 1,
+            val: "bravo",
+            count: 4,
+            mappings: &mappings,
+        };
+
+        Expression::parse(expr)?.evaluate(&scope)
+    }
+
+    fn evaluate_str(expr: &str) -> String {
+        evaluate(expr).unwrap().to_string()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(evaluate_str("($i + 1) * 2"), "4");
+        assert_eq!(evaluate_str("$count - $i - 1"), "2");
+        assert_eq!(evaluate_str("7 % 4 + -1"), "2");
+        assert_eq!(evaluate_str("3 / 2"), "1.5");
+        assert_eq!(evaluate_str("disk * 10"), "20");
+    }
+
+    #[test]
+    fn printing_whole_numbers_beyond_integers() {
+        assert_eq!(evaluate_str("-4 / 2"), "-2");
+        assert_eq!(evaluate_str("9007199254740991"), "9007199254740991");
+        assert_eq!(evaluate_str("99999999999999999999999"), "100000000000000000000000");
+    }
+
+    #[test]
+    fn strings_and_comparisons() {
+        assert_eq!(evaluate_str("name + '-' + $val"), "game-bravo");
+        assert_eq!(evaluate_str("\"disk\" + ($i + 1)"), "disk2");
+        assert_eq!(evaluate_str("$i == 1 && $val != 'alpha'"), "true");
+        assert_eq!(evaluate_str("$i >= $count || !true"), "false");
+    }
+
+    #[test]
+    fn ternaries() {
+        assert_eq!(evaluate_str("$i == 0 ? 'first' : 'other'"), "other");
+        assert_eq!(
+            evaluate_str("$i + 1 == $count ? 'last' : $i == 1 ? 'second' : 'other'"),
+            "second"
+        );
+    }
+
+    #[test]
+    fn parse_errors_report_columns() {
+        assert_eq!(
+            Expression::parse("$i + ").unwrap_err(),
+            ExpressionError::Parse {
+                column: 6,
+                message: String::from("unexpected end of expression"),
+            }
+        );
+
+        match Expression::parse("$i + $index").unwrap_err() {
+            ExpressionError::Parse { column, .. } => assert_eq!(column, 6),
+            err => panic!("unexpected error: {:?}", err),
+        }
+
+        match Expression::parse("($i + 1").unwrap_err() {
+            ExpressionError::Parse { column, .. } => assert_eq!(column, 8),
+            err => panic!("unexpected error: {:?}", err),
+        }
+
+        assert!(Expression::parse("'unterminated").is_err());
+        assert!(Expression::parse(&"(".repeat(1000)).is_err());
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("name * 2").is_err());
+        assert!(evaluate("missing + 1").is_err());
+    }
+}
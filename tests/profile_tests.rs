@@ -0,0 +1,281 @@
+#[macro_use]
+extern crate maplit;
+
+extern crate rxr;
+use rxr::config::Configuration;
+use rxr::dosbox_config::DosboxConfig;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+  "extractors": {},
+  "profiles": {
+    "installer": {
+      "command": { "cmd": "sh", "args": ["{executable}"] },
+      "executables": ["disk (?P<disk>\\d+)[/\\\\]setup\\.exe$"]
+    },
+    "mixed": {
+      "command": { "cmd": "sh", "args": ["{executable}"] },
+      "executables": ["(?P<disk>\\d+)\\.img$", "(?P<part>\\d+)\\.exe$"]
+    },
+    "doom": {
+      "command": { "cmd": "gzdoom", "args": ["{executable}"] },
+      "executables": [".*\\.wad$"],
+      "features": [
+        { "pattern": "(?P<episode>e\\d)m\\d" },
+        { "pattern": "(?P<target>[^/]*)\\.wad$" }
+      ]
+    },
+    "layered": {
+      "command": { "cmd": "dosbox", "args": ["-conf", "{dosbox_conf}"] },
+      "executables": ["(?i)game\\.exe$"],
+      "dosbox": {
+        "base": "{target}/base.conf",
+        "config": { "settings": { "cpu": { "cycles": "max" }, "render": { "aspect": "true" } } },
+        "overrides": "{target}/overrides.conf",
+        "output": "game.conf"
+      }
+    },
+    "sparse": {
+      "command": { "cmd": "dosbox", "args": ["-conf", "{dosbox_conf}"] },
+      "executables": ["(?i)game\\.exe$"],
+      "dosbox": {
+        "base": "{target}/missing-base.conf",
+        "config": { "settings": { "cpu": { "cycles": "max" } } },
+        "overrides": "{target}/missing-overrides.conf"
+      }
+    },
+    "gog": {
+      "command": { "cmd": "dosbox", "args": ["-conf", "{dosbox_conf}"] },
+      "executables": ["(?i)_(single|setup)\\.conf$"],
+      "dosbox": { "gog": true }
+    },
+    "gog-rebased": {
+      "command": { "cmd": "dosbox", "args": ["-conf", "{dosbox_conf}"] },
+      "executables": ["(?i)_(single|setup)\\.conf$"],
+      "dosbox": { "gog": true, "mount_base": "{target}" }
+    },
+    "gog-staging": {
+      "command": { "cmd": "dosbox", "args": ["-conf", "{dosbox_conf}"] },
+      "executables": ["(?i)_single\\.conf$"],
+      "dosbox": {
+        "gog": true,
+        "dialect": "staging",
+        "base": "{target}/staging.conf",
+        "config": { "settings": { "sdl": { "fulldouble": "true" } } }
+      }
+    }
+  }
+}"#;
+
+    fn config(target: &PathBuf) -> Configuration {
+        Configuration::builder()
+            .args(vec!["-d", "/data", "-o", target.to_str().unwrap(), "game.zip"])
+            .persisted("rxr.json", CONFIG)
+            .build()
+            .unwrap()
+    }
+
+    /// Creates the given files under an empty directory for a test.
+    fn test_dir(name: &str, files: &[&str]) -> PathBuf {
+        let dir = env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        dir
+    }
+
+    fn to_owned_map(map: HashMap<&str, &str>) -> HashMap<String, String> {
+        map.into_iter()
+            .map(|(key, val)| (String::from(key), String::from(val)))
+            .collect()
+    }
+
+    fn dosbox_config(profile: &str, dir: &PathBuf) -> Option<(PathBuf, DosboxConfig)> {
+        let config = config(dir);
+        let profile = &config.profiles[profile];
+        let candidates = profile.candidates(dir).unwrap();
+
+        profile
+            .dosbox_config(&candidates, &config.mappings(None), &config.environment)
+            .unwrap()
+    }
+
+    fn candidates(profile: &str, dir: &PathBuf) -> Vec<String> {
+        let config = config(dir);
+        let candidates = config.profiles[profile].candidates(dir).unwrap();
+
+        candidates
+            .iter()
+            .map(|candidate| candidate.path.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn ordering_candidates_naturally() {
+        let dir = test_dir(
+            "rxr-natural-order-tests",
+            &["Disk 10/SETUP.EXE", "Disk 2/SETUP.EXE", "Disk 1/SETUP.EXE"],
+        );
+
+        let candidates = candidates("installer", &dir);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            candidates,
+            vec!["Disk 1/SETUP.EXE", "Disk 2/SETUP.EXE", "Disk 10/SETUP.EXE"]
+        );
+    }
+
+    #[test]
+    fn ordering_by_captures_of_the_same_name() {
+        let dir = test_dir("rxr-capture-order-tests", &["b1.exe", "a2.img", "a10.img"]);
+
+        let candidates = candidates("mixed", &dir);
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(candidates, vec!["a2.img", "a10.img", "b1.exe"]);
+    }
+
+    #[test]
+    fn inserting_feature_captures() {
+        let target = PathBuf::from("/games/doom");
+        let config = config(&target);
+        let mut mappings = config.mappings(Some("doom"));
+
+        config.profiles["doom"].insert_feature_captures(&["maps/E1M1.wad"], &mut mappings);
+
+        assert_eq!(mappings.get("episode"), Some("E1"));
+        assert_eq!(mappings.get("target"), Some("/games/doom"));
+    }
+
+    #[test]
+    fn layering_dosbox_configs() {
+        let dir = test_dir("rxr-dosbox-layer-tests", &["BIN/GAME.EXE"]);
+        fs::write(
+            dir.join("base.conf"),
+            "[cpu]\ncore=auto\ncycles=auto\n[sdl]\nfullscreen=false\n[autoexec]\nmount c /base\n",
+        ).unwrap();
+        fs::write(dir.join("overrides.conf"), "[sdl]\nfullscreen=true\n[autoexec]\nmount c .\nc:\n").unwrap();
+
+        let layered = dosbox_config("layered", &dir).unwrap();
+        let sparse = dosbox_config("sparse", &dir).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(layered.0, dir.join("game.conf"));
+        assert_eq!(
+            layered.1,
+            DosboxConfig {
+                autoexec: vec![String::from("mount c ."), String::from("c:")],
+                settings: hashmap!{
+                    String::from("cpu") => to_owned_map(hashmap!{ "core" => "auto", "cycles" => "max" }),
+                    String::from("render") => to_owned_map(hashmap!{ "aspect" => "true" }),
+                    String::from("sdl") => to_owned_map(hashmap!{ "fullscreen" => "true" }),
+                },
+            }
+        );
+
+        assert_eq!(sparse.0, dir.join("rxr-dosbox.conf"));
+        assert_eq!(
+            sparse.1,
+            DosboxConfig {
+                autoexec: DosboxConfig::launcher(&dir, Path::new("BIN/GAME.EXE")).autoexec,
+                settings: hashmap!{
+                    String::from("cpu") => to_owned_map(hashmap!{ "cycles" => "max" }),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn layering_gog_configs() {
+        let dir = test_dir("rxr-gog-layer-tests", &[]);
+        let empty = test_dir("rxr-gog-layer-empty-tests", &[]);
+        fs::create_dir_all(dir.join("DOSBOX")).unwrap();
+        fs::create_dir_all(&empty).unwrap();
+        fs::write(
+            dir.join("DOSBOX/dosboxKeen.conf"),
+            "[cpu]\ncore=auto\ncycles=auto\n[autoexec]\n",
+        ).unwrap();
+        fs::write(
+            dir.join("DOSBOX/dosboxKeen_single.conf"),
+            "[cpu]\ncycles=fixed 5000\n[autoexec]\nmount C \"..\"\nmount D /cdrom -t cdrom\nc:\nkeen.exe\n",
+        ).unwrap();
+        fs::write(dir.join("DOSBOX/dosboxKeen_setup.conf"), "[autoexec]\nsetup.exe\n").unwrap();
+
+        let candidates = candidates("gog", &dir);
+        let (path, config) = dosbox_config("gog", &dir).unwrap();
+        let (_, rebased) = dosbox_config("gog-rebased", &dir).unwrap();
+        let without_candidates = dosbox_config("gog", &empty);
+
+        fs::remove_dir_all(&dir).unwrap();
+        fs::remove_dir_all(&empty).unwrap();
+
+        assert_eq!(
+            candidates,
+            vec!["DOSBOX/dosboxKeen_single.conf", "DOSBOX/dosboxKeen_setup.conf"]
+        );
+        assert_eq!(path, dir.join("rxr-dosbox.conf"));
+        assert_eq!(
+            config,
+            DosboxConfig {
+                autoexec: vec![
+                    format!("mount C \"{}\"", dir.display()),
+                    String::from("mount D /cdrom -t cdrom"),
+                    String::from("c:"),
+                    String::from("keen.exe"),
+                ],
+                settings: hashmap!{
+                    String::from("cpu") => to_owned_map(hashmap!{ "core" => "auto", "cycles" => "fixed 5000" }),
+                },
+            }
+        );
+        assert_eq!(
+            rebased.autoexec[0],
+            format!("mount C \"{}\"", dir.parent().unwrap().display())
+        );
+        assert!(without_candidates.is_none());
+    }
+
+    #[test]
+    fn translating_gog_configs_into_the_dialect_of_the_base() {
+        let dir = test_dir("rxr-dialect-layer-tests", &[]);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("staging.conf"),
+            "[sdl]\noutput=opengl\n[render]\nglshader=crt-auto\n",
+        ).unwrap();
+        fs::write(
+            dir.join("dosboxKeen_single.conf"),
+            "[sdl]\noutput=overlay\nsensitivity=50\n[render]\nscaler=normal2x\n",
+        ).unwrap();
+
+        let (_, config) = dosbox_config("gog-staging", &dir).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            config.settings,
+            hashmap!{
+                String::from("sdl") => to_owned_map(hashmap!{ "output" => "texture" }),
+                String::from("mouse") => to_owned_map(hashmap!{ "mouse_sensitivity" => "50" }),
+                String::from("render") => to_owned_map(hashmap!{ "glshader" => "crt-auto" }),
+            }
+        );
+    }
+}
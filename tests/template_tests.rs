@@ -2,7 +2,9 @@
 extern crate maplit;
 
 extern crate rxr;
-use rxr::template::Template;
+use rxr::command::{Argument, Command};
+use rxr::mappings::Mappings;
+use rxr::template::{Template, TemplateError};
 
 use std::collections::HashMap;
 
@@ -75,4 +77,89 @@ mod tests {
 
         assert_eq!(compiled, expected);
     }
+
+    #[test]
+    fn substitute_with_defaults() {
+        let template = Template::new("{key_a:-none} {key_d:-fallback} {key_e:-} {key_f}");
+        let mut mappings = get_mapping();
+        mappings.insert(String::from("key_e"), String::new());
+
+        let substituted = template.substitute(&mappings).unwrap();
+
+        assert_eq!(substituted, "val_a fallback  {key_f}");
+    }
+
+    #[test]
+    fn substitute_empty_mapping_with_default() {
+        let template = Template::new("-conf={conf:-dosbox.conf}");
+        let mut mappings = get_mapping();
+        mappings.insert(String::from("conf"), String::new());
+
+        let substituted = template.substitute(&mappings).unwrap();
+
+        assert_eq!(substituted, "-conf=dosbox.conf");
+        assert_eq!(template.keys(), vec!["conf"]);
+    }
+
+    #[test]
+    fn include_conditional_arguments_when_set() {
+        let command = Command {
+            cmd: String::from("dosbox"),
+            args: vec![
+                Argument::Value(String::from("{executable}")),
+                Argument::Conditional {
+                    when: String::from("conf"),
+                    args: vec![String::from("-conf"), String::from("{conf}")],
+                },
+            ],
+            evars: HashMap::new(),
+            wd: Command::wd_default(),
+        };
+        let args = |mappings: &Mappings| {
+            let mut command = command.clone();
+            command.apply_mappings(mappings).unwrap();
+            command
+                .args
+                .iter()
+                .flat_map(Argument::values)
+                .cloned()
+                .collect::<Vec<String>>()
+        };
+        let mut mappings = Mappings::new();
+        mappings.insert("executable", "KEEN.EXE");
+
+        assert_eq!(args(&mappings), vec!["KEEN.EXE"]);
+
+        mappings.insert("conf", "");
+        assert_eq!(args(&mappings), vec!["KEEN.EXE"]);
+
+        mappings.insert("conf", "dosbox.conf");
+        assert_eq!(args(&mappings), vec!["KEEN.EXE", "-conf", "dosbox.conf"]);
+    }
+
+    #[test]
+    fn expand_with_count_and_mappings() {
+        let template = Template::new("{{$i + 1 == $count ? key_a : $val}}");
+        let mappings = get_mapping();
+        let items = get_expansion_items();
+
+        let compiled = template.expand(&items, &mappings).unwrap();
+
+        assert_eq!(compiled, vec!["alpha", "bravo", "charlie", "val_a"]);
+    }
+
+    #[test]
+    fn validate_reports_template_columns() {
+        assert!(Template::new("{key_a} {{$i}}").validate().is_ok());
+
+        match Template::new("{key_a} {{$i +}}").validate().unwrap_err() {
+            TemplateError::Expression { column, .. } => assert_eq!(column, 15),
+            err => panic!("unexpected error: {:?}", err),
+        }
+
+        match Template::new("-drive={{$val").validate().unwrap_err() {
+            TemplateError::Unterminated { column, .. } => assert_eq!(column, 8),
+            err => panic!("unexpected error: {:?}", err),
+        }
+    }
 }